@@ -7,7 +7,11 @@
 layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
 
 layout(std430, set = 0, binding = 0) restrict writeonly buffer FFTBuffer {
-	vec4 butterfly[]; // log2(map_size) x map_size
+	vec4 butterfly[]; // log2(map_size) x map_size, for each map size in use
+};
+
+layout(push_constant) restrict readonly uniform PushConstants {
+	uint butterfly_offset; // Start of the butterfly factors for this map_size
 };
 
 /** Returns exp(j*x) assuming x >= 0. */
@@ -15,7 +19,7 @@ vec2 exp_complex(in float x) {
 	return vec2(cos(x), sin(x));
 }
 
-#define BUTTERFLY(col, stage) (butterfly[(stage)*map_size + (col)])
+#define BUTTERFLY(col, stage) (butterfly[butterfly_offset + (stage)*map_size + (col)])
 void main() {
 	const uint map_size = gl_NumWorkGroups.x * gl_WorkGroupSize.x * 2;
 	const uint col = gl_GlobalInvocationID.x;   // Column in row
@@ -16,11 +16,12 @@ layout(std430, set = 0, binding = 0) restrict readonly buffer ButterflyFactorBuf
 };
 
 layout(std430, set = 0, binding = 1) restrict buffer FFTBuffer {
-	vec2 data[]; // map_size x map_size x num_spectra x 2 * num_cascades
+	vec2 data[]; // map_size x map_size x num_spectra x 2, for each cascade at its own map_size
 };
 
 layout(push_constant) restrict readonly uniform PushConstants {
-	uint cascade_index;
+	uint data_offset;      // Start of this cascade's FFT data
+	uint butterfly_offset; // Start of the butterfly factors for this cascade's map_size
 };
 
 shared vec2 row_shared[2 * MAX_MAP_SIZE]; // "Ping-pong" shared buffer for a single row
@@ -31,13 +32,13 @@ vec2 mul_complex(in vec2 a, in vec2 b) {
 }
 
 #define ROW_SHARED(col, pingpong) (row_shared[(pingpong)*MAX_MAP_SIZE + (col)])
-#define BUTTERFLY(col, stage)     (butterfly[(stage)*map_size + (col)])
-#define DATA_IN(id, layer)  (data[(id.z)*map_size*map_size*NUM_SPECTRA*2 +                             0 + (layer)*map_size*map_size + (id.y)*map_size + (id.x)])
-#define DATA_OUT(id, layer) (data[(id.z)*map_size*map_size*NUM_SPECTRA*2 + NUM_SPECTRA*map_size*map_size + (layer)*map_size*map_size + (id.y)*map_size + (id.x)])
+#define BUTTERFLY(col, stage)     (butterfly[butterfly_offset + (stage)*map_size + (col)])
+#define DATA_IN(id, layer)  (data[data_offset +                             0 + (layer)*map_size*map_size + (id.y)*map_size + (id.x)])
+#define DATA_OUT(id, layer) (data[data_offset + NUM_SPECTRA*map_size*map_size + (layer)*map_size*map_size + (id.y)*map_size + (id.x)])
 void main() {
 	const uint map_size = gl_NumWorkGroups.y * gl_WorkGroupSize.y;
 	const uint num_stages = findMSB(map_size); // Equivalent: log2(map_size) (assuming map_size is a power of 2)
-	const uvec3 id = uvec3(gl_GlobalInvocationID.xy, cascade_index); // col, row, cascade
+	const uvec2 id = gl_GlobalInvocationID.xy; // col, row
 	const uint col = id.x;
 	const uint spectrum = gl_GlobalInvocationID.z; // The spectrum in the buffer to perform FFT on.
 	
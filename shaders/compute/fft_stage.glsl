@@ -0,0 +1,55 @@
+#[compute]
+#version 460
+/** 
+ * A single stage of the Stockham FFT in `fft_compute.glsl`, for map sizes whose rows don't fit
+ * in one work group. Each stage ping-pongs between the two halves of the FFT buffer in global memory.
+ * Source: http://wwwa.pikara.ne.jp/okojisan/otfft-en/stockham3.html
+ */
+
+#define NUM_SPECTRA (4U)
+
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+layout(std430, set = 0, binding = 0) restrict readonly buffer ButterflyFactorBuffer {
+	vec4 butterfly[]; // log2(map_size) x map_size
+};
+
+layout(std430, set = 0, binding = 1) restrict buffer FFTBuffer {
+	vec2 data[]; // map_size x map_size x num_spectra x 2, for each cascade at its own map_size
+};
+
+layout(push_constant) restrict readonly uniform PushConstants {
+	uint data_offset;      // Start of this cascade's FFT data
+	uint butterfly_offset; // Start of the butterfly factors for this cascade's map_size
+	uint stage; // A stage past the last copies the input half to the output half.
+};
+
+/** Returns (a0 + j*a1)(b0 + j*b1) */
+vec2 mul_complex(in vec2 a, in vec2 b) {
+	return vec2(a.x*b.x - a.y*b.y, a.x*b.y + a.y*b.x);
+}
+
+#define BUTTERFLY(col, stage)       (butterfly[butterfly_offset + (stage)*map_size + (col)])
+#define DATA(half, layer, row, col) (data[data_offset + (half)*NUM_SPECTRA*map_size*map_size + (layer)*map_size*map_size + (row)*map_size + (col)])
+void main() {
+	const uint map_size = gl_NumWorkGroups.x * gl_WorkGroupSize.x;
+	const uint num_stages = findMSB(map_size); // Equivalent: log2(map_size) (assuming map_size is a power of 2)
+	const uint col = gl_GlobalInvocationID.x;
+	const uint row = gl_GlobalInvocationID.y;
+	const uint spectrum = gl_GlobalInvocationID.z; // The spectrum in the buffer to perform FFT on.
+
+	if (stage >= num_stages) {
+		DATA(1, spectrum, row, col) = DATA(0, spectrum, row, col);
+		return;
+	}
+
+	uvec2 buf_idx = uvec2(stage % 2, (stage + 1) % 2); // x=read half, y=write half
+	vec4 butterfly_data = BUTTERFLY(col, stage);
+
+	uvec2 read_indices = uvec2(floatBitsToUint(butterfly_data.xy));
+	vec2 twiddle_factor = butterfly_data.zw;
+
+	vec2 upper = DATA(buf_idx[0], spectrum, row, read_indices[0]);
+	vec2 lower = DATA(buf_idx[0], spectrum, row, read_indices[1]);
+	DATA(buf_idx[1], spectrum, row, col) = upper + mul_complex(lower, twiddle_factor);
+}
@@ -2,7 +2,8 @@
 #version 460
 /** 
  * Unpacks the IFFT outputs from the modulation stage and creates
- * the output displacement and normal maps.
+ * the output displacement and normal maps. Cascades computed at a lower
+ * map size than the maps are bilinearly upsampled.
  */
 
 #define TILE_SIZE   (16U)
@@ -14,7 +15,7 @@ layout(rgba16f, set = 0, binding = 0) restrict writeonly uniform image2DArray di
 layout(rgba16f, set = 0, binding = 1) restrict uniform image2DArray normal_map;
 
 layout(std430, set = 1, binding = 0) restrict buffer FFTBuffer {
-	vec2 data[]; // map_size x map_size x num_spectra x 2 * num_cascades
+	vec2 data[]; // map_size x map_size x num_spectra x 2, for each cascade at its own map_size
 };
 
 layout(push_constant) restrict readonly uniform PushConstants {
@@ -22,39 +23,55 @@ layout(push_constant) restrict readonly uniform PushConstants {
 	float whitecap;
 	float foam_grow_rate;
 	float foam_decay_rate;
+	uint data_offset; // Start of this cascade's FFT data
+	uint map_size;    // Map size the cascade's FFT was computed at
 };
 
-// Tiling doesn't provide much of a benefit here (but it does a *little*)
-shared vec2 tile[NUM_SPECTRA][TILE_SIZE][TILE_SIZE];
-
 // Note: There is an assumption that the FFT does not transpose a second time. Thus,
 //       we access the FFT buffer at an offset of NUM_LAYERS*map_size*map_size
-#define FFT_DATA(id, layer) (data[(id.z)*map_size*map_size*NUM_SPECTRA*2 + NUM_SPECTRA*map_size*map_size + (layer)*map_size*map_size + (id).y*map_size + (id).x])
-void main() {
-	const uint map_size = gl_NumWorkGroups.x * gl_WorkGroupSize.x;
-	const uvec3 id_local = gl_LocalInvocationID;
-	const ivec3 id = ivec3(gl_GlobalInvocationID.xy, cascade_index);
+#define FFT_DATA(id, layer) (data[data_offset + NUM_SPECTRA*map_size*map_size + (layer)*map_size*map_size + (id).y*map_size + (id).x])
+
+/** Returns one texel of the IFFT output, wrapped to the cascade's map size. */
+vec2 fft_texel(in ivec2 id, in uint layer) {
+	id = ivec2(mod(id, ivec2(map_size)));
 	// Multiplying output of inverse FFT by below factor is equivalent to ifftshift()
 	const float sign_shift = -2*((id.x & 1) ^ (id.y & 1)) + 1; // Equivalent: (-1^id.x)(-1^id.y)
+	return FFT_DATA(id, layer) * sign_shift;
+}
 
-	tile[id_local.z*2][id_local.y][id_local.x] = FFT_DATA(id, id_local.z*2);
-	tile[id_local.z*2 + 1][id_local.y][id_local.x] = FFT_DATA(id, id_local.z*2 + 1);
-	barrier();
+/** Bilinearly samples the IFFT output at the center of an output texel. */
+vec2 fft_sample(in ivec2 id, in uint layer, in float scale) {
+	vec2 pos = (vec2(id) + 0.5)*scale - 0.5;
+	ivec2 id0 = ivec2(floor(pos));
+	vec2 t = pos - vec2(id0);
+	vec2 top = mix(fft_texel(id0, layer), fft_texel(id0 + ivec2(1, 0), layer), t.x);
+	vec2 bottom = mix(fft_texel(id0 + ivec2(0, 1), layer), fft_texel(id0 + ivec2(1, 1), layer), t.x);
+	return mix(top, bottom, t.y);
+}
+
+void main() {
+	const uint output_size = gl_NumWorkGroups.x * gl_WorkGroupSize.x;
+	const float scale = float(map_size) / float(output_size);
+	const uvec3 id_local = gl_LocalInvocationID;
+	const ivec3 id = ivec3(gl_GlobalInvocationID.xy, cascade_index);
 
 	// Half of all threads writes to displacement map while other half writes to normal map.
 	switch (id_local.z) {
 		case 0:
-			float hx = tile[0][id_local.y][id_local.x].x;
-			float hy = tile[0][id_local.y][id_local.x].y;
-			float hz = tile[1][id_local.y][id_local.x].x;
-			imageStore(displacement_map, id, vec4(hx, hy, hz, 0) * sign_shift);
+			vec2 spectrum0 = fft_sample(id.xy, 0, scale);
+			float hx = spectrum0.x;
+			float hy = spectrum0.y;
+			float hz = fft_sample(id.xy, 1, scale).x;
+			imageStore(displacement_map, id, vec4(hx, hy, hz, 0));
 			break;
 		case 1:
-			float dhy_dx = tile[1][id_local.y][id_local.x].y * sign_shift;
-			float dhy_dz = tile[2][id_local.y][id_local.x].x * sign_shift;
-			float dhx_dx = tile[2][id_local.y][id_local.x].y * sign_shift;
-			float dhz_dz = tile[3][id_local.y][id_local.x].x * sign_shift;
-			float dhz_dx = tile[3][id_local.y][id_local.x].y * sign_shift;
+			vec2 spectrum2 = fft_sample(id.xy, 2, scale);
+			vec2 spectrum3 = fft_sample(id.xy, 3, scale);
+			float dhy_dx = fft_sample(id.xy, 1, scale).y;
+			float dhy_dz = spectrum2.x;
+			float dhx_dx = spectrum2.y;
+			float dhz_dz = spectrum3.x;
+			float dhz_dx = spectrum3.y;
 
 			float jacobian = (1.0 + dhx_dx) * (1.0 + dhz_dz) - dhz_dx*dhz_dx;
 			float foam_factor = -min(0, jacobian - whitecap);
@@ -67,4 +84,4 @@ void main() {
 			imageStore(normal_map, id, vec4(gradient, dhx_dx, foam));
 			break;
 	}
-}
\ No newline at end of file
+}
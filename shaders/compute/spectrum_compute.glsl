@@ -1,19 +1,40 @@
 #[compute]
 #version 460
 /**
- * Generates a 2D texture representing the JONSWAP wave spectra
- * w/ Hasselmann directional spreading.
+ * Generates a 2D texture representing the wave spectra (Phillips, Pierson-Moskowitz,
+ * JONSWAP, TMA or Donelan-Banner) w/ directional spreading (Hasselmann, Mitsuyasu,
+ * Donelan-Banner, positive cosine squared or a custom table), summed with an optional swell.
  *
  * Sources: Jerry Tessendorf - Simulating Ocean Water
  *          Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+ *          Donelan, Hamilton & Hui - Directional Spectra of Wind-Generated Waves
+ *          Yoshimi Goda - Random Seas and Design of Maritime Structures
  */
 
 #define PI (3.141592653589793)
-#define G  (9.81)
+
+// Must match SpectrumModel in wave_cascade_parameters.rs
+#define SPECTRUM_PHILLIPS          (0U)
+#define SPECTRUM_PIERSON_MOSKOWITZ (1U)
+#define SPECTRUM_JONSWAP           (2U)
+#define SPECTRUM_TMA               (3U)
+#define SPECTRUM_DONELAN_BANNER    (4U)
+
+// Must match DirectionalSpreading in wave_cascade_parameters.rs
+#define SPREADING_HASSELMANN              (0U)
+#define SPREADING_MITSUYASU               (1U)
+#define SPREADING_DONELAN_BANNER          (2U)
+#define SPREADING_POSITIVE_COSINE_SQUARED (3U)
+#define SPREADING_CUSTOM                  (4U)
+#define SPREADING_TABLE_SIZE              (32U)
 
 layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
 
-layout(rgba16f, set = 0, binding = 0) restrict writeonly uniform image2DArray spectrum;
+layout(rgba32f, set = 0, binding = 0) restrict writeonly uniform image2DArray spectrum;
+
+layout(std430, set = 1, binding = 0) restrict readonly buffer SpreadingTableBuffer {
+	float spreading_table[]; // SPREADING_TABLE_SIZE x spectrum layers, normalized over [-PI, PI]
+};
 
 layout(push_constant) restrict readonly uniform PushConstants {
 	ivec2 seed;
@@ -27,6 +48,18 @@ layout(push_constant) restrict readonly uniform PushConstants {
 	float detail;
 	float spread;
 	uint cascade_index;
+	uint spectrum_model;
+	float gamma; // Peak enhancement factor
+	float beta;  // Peak shape (JONSWAP/TMA only)
+	uint spreading_function;
+	float gravity;
+	vec2 sigma; // Peak width below and above the peak frequency
+	float swell_height; // Significant wave height of the swell, 0 disables it
+	float swell_peak_frequency;
+	float swell_angle;
+	float swell_spreading; // cos-2s shaping parameter of the swell
+	vec2 wavenumber_cutoff; // Band of k this cascade covers, so overlapping cascades don't add up
+	uint spectrum_layer; // Each cascade has two layers so it can transition between spectra
 };
 
 // --- HELPER FUNCTIONS ---
@@ -58,8 +91,8 @@ vec2 conj_complex(in vec2 x) {
 vec2 dispersion_relation(in float k) {
 	float a = k*depth;
 	float b = tanh(a);
-	float dispersion_relation = sqrt(G*k*b);
-	float d_dispersion_relation = 0.5*G * (b + a*(1.0 - b*b)) / dispersion_relation;
+	float dispersion_relation = sqrt(gravity*k*b);
+	float d_dispersion_relation = 0.5*gravity * (b + a*(1.0 - b*b)) / dispersion_relation;
 
 	// Return both the dispersion relation and its derivative w.r.t. k
 	return vec2(dispersion_relation, d_dispersion_relation);
@@ -77,27 +110,132 @@ float longuet_higgins_function(in float s, in float theta) {
 	return longuet_higgins_normalization(s) * pow(abs(cos(theta*0.5)), 2.0*s);
 }
 
+/** Extra shaping parameter w/ swell, which narrows the cos-2s spreading functions. */
+float swell_shaping(in float w, in float w_p) {
+	return 16.0 * tanh(w_p / w) * swell*swell;
+}
+
 // Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
 float hasselmann_directional_spread(in float w, in float w_p, in float wind_speed, in float theta) {
 	float p = w / w_p;
-	float s = (w <= w_p) ? 6.97*pow(abs(p), 4.06) : 9.77*pow(abs(p), -2.33 - 1.45*(wind_speed*w_p/G - 1.17)); // Shaping parameter
-	float s_xi = 16.0 * tanh(w_p / w) * swell*swell; // Shaping parameter w/ swell
-    return longuet_higgins_function(s + s_xi, theta - angle);
+	float s = (w <= w_p) ? 6.97*pow(abs(p), 4.06) : 9.77*pow(abs(p), -2.33 - 1.45*(wind_speed*w_p/gravity - 1.17)); // Shaping parameter
+    return longuet_higgins_function(s + swell_shaping(w, w_p), theta);
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+float mitsuyasu_directional_spread(in float w, in float w_p, in float wind_speed, in float theta) {
+	float s_p = 11.5 * pow(gravity / (w_p*wind_speed), 2.5);
+	float s = s_p * pow(w / w_p, (w <= w_p) ? 5.0 : -2.5); // Shaping parameter
+	return longuet_higgins_function(s + swell_shaping(w, w_p), theta);
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+float donelan_banner_directional_spread(in float w, in float w_p, in float theta) {
+	float p = w / w_p;
+	float beta_s;
+	if (p < 0.95) {
+		beta_s = 2.61 * pow(p, 1.3);
+	} else if (p < 1.6) {
+		beta_s = 2.28 * pow(p, -1.3);
+	} else {
+		beta_s = pow(10.0, -0.4 + 0.8393*exp(-0.567*log(p*p)));
+	}
+	float sech = 1.0 / cosh(beta_s * theta);
+	return beta_s / (2.0 * tanh(beta_s * PI)) * sech*sech;
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+float positive_cosine_squared_directional_spread(in float theta) {
+	float c = cos(theta);
+	return (abs(theta) < 0.5*PI) ? 2.0/PI * c*c : 0.0;
+}
+
+/** Linearly interpolates the cascade's custom spreading table, which covers |theta| from 0 to PI. */
+float custom_directional_spread(in float theta) {
+	float x = abs(theta) / PI * float(SPREADING_TABLE_SIZE - 1U);
+	uint i = min(uint(x), SPREADING_TABLE_SIZE - 2U);
+	uint offset = spectrum_layer * SPREADING_TABLE_SIZE;
+	return mix(spreading_table[offset + i], spreading_table[offset + i + 1U], x - float(i));
+}
+
+/** Returns the spreading function at theta, relative to the wind direction. */
+float directional_spread(in float w, in float w_p, in float theta) {
+	theta = mod(theta - angle + PI, 2.0*PI) - PI; // Wrap to [-PI, PI)
+	switch (spreading_function) {
+		case SPREADING_MITSUYASU:               return mitsuyasu_directional_spread(w, w_p, wind_speed, theta);
+		case SPREADING_DONELAN_BANNER:          return donelan_banner_directional_spread(w, w_p, theta);
+		case SPREADING_POSITIVE_COSINE_SQUARED: return positive_cosine_squared_directional_spread(theta);
+		case SPREADING_CUSTOM:                  return custom_directional_spread(theta);
+		default:                                return hasselmann_directional_spread(w, w_p, wind_speed, theta);
+	}
+}
+
+// Source: Jerry Tessendorf - Simulating Ocean Water (rewritten in terms of w for deep water)
+float phillips_spectrum(in float w, in float alpha) {
+	float w_0 = gravity / wind_speed;
+	return (alpha * gravity*gravity) / pow(w, 5) * exp(-pow(w_0/w, 4));
+}
+
+// Source: https://wikiwaves.org/Ocean-Wave_Spectra#Pierson-Moskowitz_Spectrum
+float pierson_moskowitz_spectrum(in float w, in float w_p, in float alpha) {
+	return (alpha * gravity*gravity) / pow(w, 5) * exp(-1.25 * pow(w_p/w, 4));
+}
+
+/** Returns the peak enhancement exponent of JONSWAP-like spectra. */
+float peak_enhancement_exponent(in float w, in float w_p) {
+	float s = (w <= w_p) ? sigma.x : sigma.y;
+	return exp(-(w-w_p)*(w-w_p) / (2.0 * s*s * w_p*w_p));
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+float JONSWAP_spectrum(in float w, in float w_p, in float alpha) {
+	float r = peak_enhancement_exponent(w, w_p);
+	return (alpha * gravity*gravity) / pow(w, 5) * exp(-beta * pow(w_p/w, 4)) * pow(gamma, r);
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+float kitaigorodskii_depth_attenuation(in float w) {
+	float w_h = min(w * sqrt(depth / gravity), 2.0);
+	return (w_h <= 1.0) ? 0.5*w_h*w_h : 1.0 - 0.5*(2.0-w_h)*(2.0-w_h);
 }
 
 // Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
 float TMA_spectrum(in float w, in float w_p, in float alpha) {
-	const float beta = 1.25;
-	const float gamma = 3.3; // Spectral peak shape constant
-	
-	float sigma = (w <= w_p) ? 0.07 : 0.09;
-	float r = exp(-(w-w_p)*(w-w_p) / (2.0 * sigma*sigma * w_p*w_p));
-	float jonswap_spectrum = (alpha * G*G) / pow(w, 5) * exp(-beta * pow(w_p/w, 4)) * pow(gamma, r);
+	return JONSWAP_spectrum(w, w_p, alpha) * kitaigorodskii_depth_attenuation(w);
+}
 
-	float w_h = min(w * sqrt(depth / G), 2.0);
-	float kitaigorodskii_depth_attenuation = (w_h <= 1.0) ? 0.5*w_h*w_h : 1.0 - 0.5*(2.0-w_h)*(2.0-w_h);
+// Source: Donelan, Hamilton & Hui - Directional Spectra of Wind-Generated Waves
+float donelan_banner_spectrum(in float w, in float w_p, in float alpha) {
+	float r = peak_enhancement_exponent(w, w_p);
+	return (alpha * gravity*gravity) / (pow(w, 4) * w_p) * exp(-pow(w_p/w, 4)) * pow(gamma, r);
+}
+
+float omnidirectional_spectrum(in float w, in float w_p, in float alpha) {
+	switch (spectrum_model) {
+		case SPECTRUM_PHILLIPS:          return phillips_spectrum(w, alpha);
+		case SPECTRUM_PIERSON_MOSKOWITZ: return pierson_moskowitz_spectrum(w, w_p, alpha);
+		case SPECTRUM_JONSWAP:           return JONSWAP_spectrum(w, w_p, alpha);
+		case SPECTRUM_DONELAN_BANNER:    return donelan_banner_spectrum(w, w_p, alpha);
+		default:                         return TMA_spectrum(w, w_p, alpha);
+	}
+}
+
+// --- SWELL ---
+// Source: Yoshimi Goda - Random Seas and Design of Maritime Structures (JONSWAP in terms of H_s and T_p)
+float swell_spectrum(in float w) {
+	if (swell_height <= 0.0) return 0.0;
+	const float swell_gamma = 3.3;
+	float w_p = swell_peak_frequency;
+	float s = (w <= w_p) ? 0.07 : 0.09;
+	float r = exp(-(w-w_p)*(w-w_p) / (2.0 * s*s * w_p*w_p));
+	float bretschneider = 0.3125 * swell_height*swell_height * pow(w_p, 4) / pow(w, 5) * exp(-1.25 * pow(w_p/w, 4));
+	// The swell feels the bottom only when the wind sea does
+	float depth_attenuation = (spectrum_model == SPECTRUM_TMA) ? kitaigorodskii_depth_attenuation(w) : 1.0;
+	return bretschneider * (1.0 - 0.287*log(swell_gamma)) * pow(swell_gamma, r) * depth_attenuation;
+}
 
-	return jonswap_spectrum * kitaigorodskii_depth_attenuation;
+float swell_directional_spread(in float theta) {
+	return longuet_higgins_function(swell_spreading, theta - swell_angle);
 }
 
 vec2 get_spectrum_amplitude(in ivec2 id, in ivec2 map_size) {
@@ -105,18 +243,22 @@ vec2 get_spectrum_amplitude(in ivec2 id, in ivec2 map_size) {
 	vec2 k_vec = (id - map_size*0.5)*dk; // Wave direction
 	float k = length(k_vec) + 1e-6;
 	float theta = atan(k_vec.x, k_vec.y);
+	if (k < wavenumber_cutoff.x || k >= wavenumber_cutoff.y) return vec2(0.0);
 
 	vec2 dispersion = dispersion_relation(k);
 	float w = dispersion[0];
 	float w_norm = dispersion[1] / k * dk.x*dk.y;
-	float s = TMA_spectrum(w, peak_frequency, alpha);
-	float d = mix(0.5/PI, hasselmann_directional_spread(w, peak_frequency, wind_speed, theta), 1.0 - spread) * exp(-(1.0-detail)*(1.0-detail) * k*k);
-	return gaussian(hash(uvec2(id + seed))) * sqrt(2.0 * s * d * w_norm);
+	float s = omnidirectional_spectrum(w, peak_frequency, alpha);
+	float d = mix(0.5/PI, directional_spread(w, peak_frequency, theta), 1.0 - spread);
+	float swell_s = swell_spectrum(w);
+	float swell_d = swell_directional_spread(theta);
+	float detail_falloff = exp(-(1.0-detail)*(1.0-detail) * k*k);
+	return gaussian(hash(uvec2(id + seed))) * sqrt(2.0 * (s*d + swell_s*swell_d) * detail_falloff * w_norm);
 }
 
 void main() {
-	const ivec2 dims = imageSize(spectrum).xy;
-	const ivec3 id = ivec3(gl_GlobalInvocationID.xy, cascade_index);
+	const ivec2 dims = ivec2(gl_NumWorkGroups.xy * gl_WorkGroupSize.xy); // The cascade's map size, at most the texture's
+	const ivec3 id = ivec3(gl_GlobalInvocationID.xy, spectrum_layer);
 	const ivec2 id0 = id.xy;
 	const ivec2 id1 = ivec2(mod(-id0, dims));
 
@@ -10,22 +10,26 @@
  */
 
 #define PI          (3.141592653589793)
-#define G           (9.81)
 #define NUM_SPECTRA (4U)
 
 layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
 
-layout(rgba16f, set = 0, binding = 0) restrict readonly uniform image2DArray spectrum;
+layout(rgba32f, set = 0, binding = 0) restrict readonly uniform image2DArray spectrum;
 
 layout(std430, set = 1, binding = 0) restrict writeonly buffer FFTBuffer {
-	vec2 data[]; // map_size x map_size x num_spectra x 2 * num_cascades
+	vec2 data[]; // map_size x map_size x num_spectra x 2, for each cascade at its own map_size
 };
 
 layout(push_constant) restrict readonly uniform PushConstants {
 	vec2 tile_length;
 	float depth;
 	float time;
-	uint cascade_index;
+	uint data_offset; // Start of this cascade's FFT data
+	float gravity;
+	uint spectrum_layer;
+	uint target_spectrum_layer;
+	float transition; // Blend from spectrum_layer to target_spectrum_layer
+	float loop_period; // The ocean repeats every loop_period seconds, 0 disables looping
 };
 
 /** Returns exp(j*x) assuming x >= 0. */
@@ -46,22 +50,29 @@ vec2 conj_complex(in vec2 x) {
 
 // Jerry Tessendorf - Source: Simulating Ocean Water
 float dispersion_relation(in float k) {
-	return sqrt(G*k*tanh(k*depth));
+	float w = sqrt(gravity*k*tanh(k*depth));
+	if (loop_period > 0.0) {
+		// Quantize to multiples of the loop's fundamental frequency so every wave repeats in sync
+		float w_0 = 2.0*PI / loop_period;
+		w = round(w / w_0) * w_0;
+	}
+	return w;
 }
 
-#define FFT_DATA(id, layer) (data[(id.z)*map_size*map_size*NUM_SPECTRA*2 + (layer)*map_size*map_size + (id.y)*map_size + (id.x)])
+#define FFT_DATA(id, layer) (data[data_offset + (layer)*map_size*map_size + (id.y)*map_size + (id.x)])
 void main() {
 	const uint map_size = gl_NumWorkGroups.x * gl_WorkGroupSize.x;
 	const uint num_stages = findMSB(map_size); // Equivalent: log2(map_size) (assuming map_size is a power of 2)
-	const ivec2 dims = imageSize(spectrum).xy;
-	const ivec3 id = ivec3(gl_GlobalInvocationID.xy, cascade_index);
+	const ivec2 dims = ivec2(map_size); // The cascade's map size, at most the spectrum texture's
+	const ivec2 id = ivec2(gl_GlobalInvocationID.xy);
 
 	vec2 k_vec = (id.xy - dims*0.5)*2.0*PI / tile_length; // Wave direction
 	float k = length(k_vec) + 1e-6;
 	vec2 k_unit = k_vec / k;
 
 	// --- WAVE SPECTRUM MODULATION ---
-	vec4 h0 = imageLoad(spectrum, id); // xy=h0(k), zw=conj(h0(-k))
+	// Both spectra share the same seed, so blending their amplitudes keeps every wave's phase
+	vec4 h0 = mix(imageLoad(spectrum, ivec3(id.xy, spectrum_layer)), imageLoad(spectrum, ivec3(id.xy, target_spectrum_layer)), transition); // xy=h0(k), zw=conj(h0(-k))
 	float dispersion = dispersion_relation(k) * time;
 	vec2 modulation = exp_complex(dispersion);
 	// Note: h respects the complex conjugation property
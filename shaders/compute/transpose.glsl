@@ -15,24 +15,24 @@ layout(std430, set = 0, binding = 0) restrict readonly buffer ButterflyFactorBuf
 }; 
 
 layout(std430, set = 0, binding = 1) restrict buffer FFTBuffer {
-	vec2 data[]; // map_size x map_size x num_spectra x 2 * num_cascades
+	vec2 data[]; // map_size x map_size x num_spectra x 2, for each cascade at its own map_size
 };
 
 layout(push_constant) restrict readonly uniform PushConstants {
-	uint cascade_index;
+	uint data_offset; // Start of this cascade's FFT data
 };
 
 shared vec2 tile[TILE_SIZE][TILE_SIZE+1];
 
-#define DATA_IN(id, layer)  (data[(id.z)*map_size*map_size*NUM_SPECTRA*2 + NUM_SPECTRA*map_size*map_size + (layer)*map_size*map_size + (id.y)*map_size + (id.x)])
-#define DATA_OUT(id, layer) (data[(id.z)*map_size*map_size*NUM_SPECTRA*2 +                             0 + (layer)*map_size*map_size + (id.y)*map_size + (id.x)])
+#define DATA_IN(id, layer)  (data[data_offset + NUM_SPECTRA*map_size*map_size + (layer)*map_size*map_size + (id.y)*map_size + (id.x)])
+#define DATA_OUT(id, layer) (data[data_offset +                             0 + (layer)*map_size*map_size + (id.y)*map_size + (id.x)])
 void main() {
 	const uint map_size = gl_NumWorkGroups.x * gl_WorkGroupSize.x;
 	const uvec2 id_block = gl_WorkGroupID.xy;
 	const uvec2 id_local = gl_LocalInvocationID.xy;
 	const uint spectrum = gl_GlobalInvocationID.z;
 
-	uvec3 id = uvec3(gl_GlobalInvocationID.xy, cascade_index);
+	uvec2 id = gl_GlobalInvocationID.xy;
 	tile[id_local.y][id_local.x] = DATA_IN(id, spectrum);
 	barrier();
 
@@ -0,0 +1,113 @@
+use godot::prelude::*;
+use godot::classes::{Engine, INode3D, Node3D, RigidBody3D};
+use crate::ocean::Ocean;
+
+/// Floats its parent `RigidBody3D` on an `Ocean`. Each hull sample point carries an equal share of
+/// the hull volume and pushes the body up by the weight of the water it displaces, using the same
+/// cascades that `water.gdshader` renders. Submerged points also apply drag so bodies settle
+/// instead of bobbing forever.
+#[derive(GodotClass)]
+#[class(tool, base=Node3D)]
+pub struct Buoyancy3D {
+    #[export]
+    ocean: Option<Gd<Ocean>>,
+    /// Hull sample points, local to this node.
+    #[export]
+    sample_points: PackedVector3Array,
+    /// Volume of water displaced by the whole hull when fully submerged, in m³.
+    #[export(range = (0.0, 100.0, or_greater))]
+    hull_volume: real,
+    /// Depth below the surface at which a sample point counts as fully submerged, in m.
+    #[export(range = (0.01, 10.0, or_greater))]
+    submersion_depth: real,
+    /// Density of the water in kg/m³.
+    #[export(range = (0.0, 2000.0, or_greater))]
+    water_density: real,
+    /// Drag against the velocity of each submerged point, for the whole hull when fully submerged.
+    #[export(range = (0.0, 100.0, or_greater))]
+    linear_drag: real,
+    /// Drag against the body's rotation, for the whole hull when fully submerged.
+    #[export(range = (0.0, 100.0, or_greater))]
+    angular_drag: real,
+    submerged_ratio: real,
+    base: Base<Node3D>
+}
+
+#[godot_api]
+impl INode3D for Buoyancy3D {
+    fn init(base: Base<Node3D>) -> Self {
+        Self {
+            ocean: None,
+            sample_points: PackedVector3Array::from(&[Vector3::ZERO]),
+            hull_volume: 1.0,
+            submersion_depth: 1.0,
+            water_density: 1000.0,
+            linear_drag: 1.0,
+            angular_drag: 1.0,
+            submerged_ratio: 0.0,
+            base,
+        }
+    }
+
+    fn get_configuration_warnings(&self) -> PackedStringArray {
+        let mut s = PackedStringArray::new();
+        if self.get_body().is_none() {
+            s.push("Buoyancy3D must be a child of a RigidBody3D");
+        }
+        if self.ocean.is_none() {
+            s.push("No ocean set");
+        }
+        if self.sample_points.is_empty() {
+            s.push("No sample points set");
+        }
+        s
+    }
+
+    fn physics_process(&mut self, _delta: f64) {
+        if Engine::singleton().is_editor_hint() || self.sample_points.is_empty() {
+            return;
+        }
+        let (Some(mut body), Some(mut ocean)) = (self.get_body(), self.ocean.clone()) else {
+            return;
+        };
+
+        let origin = body.get_global_position();
+        let gravity = body.get_gravity();
+        let linear_velocity = body.get_linear_velocity();
+        let angular_velocity = body.get_angular_velocity();
+        let point_share = 1.0 / self.sample_points.len() as real;
+
+        let mut submerged_ratio = 0.0;
+        for point in self.sample_points.as_slice() {
+            let point = self.base().to_global(*point);
+            let depth = ocean.bind_mut().get_wave_height(Vector2::new(point.x, point.z)) - point.y;
+            if depth <= 0.0 {
+                continue;
+            }
+            let submerged = (depth / self.submersion_depth).min(1.0) * point_share;
+            submerged_ratio += submerged;
+
+            let offset = point - origin;
+            let point_velocity = linear_velocity + angular_velocity.cross(offset);
+            // Archimedes: the displaced water's weight acts against gravity
+            let buoyancy = -gravity * self.water_density * self.hull_volume * submerged;
+            let drag = -point_velocity * self.linear_drag * submerged;
+            body.apply_force_ex(buoyancy + drag).position(offset).done();
+        }
+        body.apply_torque(-angular_velocity * self.angular_drag * submerged_ratio);
+        self.submerged_ratio = submerged_ratio;
+    }
+}
+
+#[godot_api]
+impl Buoyancy3D {
+    /// Returns how much of the hull was under water during the last physics tick, from 0 to 1.
+    #[func]
+    pub fn get_submerged_ratio(&self) -> real {
+        self.submerged_ratio
+    }
+
+    fn get_body(&self) -> Option<Gd<RigidBody3D>> {
+        self.base().get_parent()?.try_cast::<RigidBody3D>().ok()
+    }
+}
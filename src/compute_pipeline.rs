@@ -0,0 +1,187 @@
+use std::fmt;
+
+use godot::prelude::*;
+use godot::classes::RenderingDevice;
+use crate::rendering_context::RenderingContext;
+
+/// Why a `ComputePipeline` refused to record a dispatch.
+#[derive(Debug, PartialEq)]
+pub(crate) enum DispatchError {
+    /// The rendering context has no device.
+    NoDevice,
+    /// No descriptor sets were given and the pipeline has none of its own.
+    NoDescriptorSets,
+    /// A work group count is 0, which the device rejects.
+    EmptyDispatch { groups: [u32; 3] },
+    /// The indirect dispatch buffer is not a valid RID.
+    InvalidIndirectBuffer,
+    /// Indirect dispatch arguments must start on a 4 byte boundary.
+    UnalignedIndirectOffset { offset: u32 },
+}
+
+impl fmt::Display for DispatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DispatchError::NoDevice => write!(f, "Rendering context has no device"),
+            DispatchError::NoDescriptorSets => write!(f, "Must specify at least one descriptor set"),
+            DispatchError::EmptyDispatch { groups } => write!(f, "Cannot dispatch {groups:?} work groups"),
+            DispatchError::InvalidIndirectBuffer => write!(f, "Indirect dispatch buffer is not a valid RID"),
+            DispatchError::UnalignedIndirectOffset { offset } => write!(f, "Indirect dispatch offset {offset} is not a multiple of 4"),
+        }
+    }
+}
+
+impl std::error::Error for DispatchError {}
+
+/// A compute shader pipeline with the work groups and descriptor sets it was created with,
+/// see `RenderingContext::create_pipeline`. The pipeline itself is freed with the context.
+#[derive(Clone, Debug)]
+pub(crate) struct ComputePipeline {
+    pipeline: Rid,
+    block_dimensions: [u32; 3],
+    descriptor_sets: Vec<Rid>,
+}
+
+impl ComputePipeline {
+    pub fn new(pipeline: Rid, block_dimensions: [u32; 3], descriptor_sets: Vec<Rid>) -> Self {
+        Self { pipeline, block_dimensions, descriptor_sets }
+    }
+
+    /// Records a dispatch of the pipeline's own work groups. Empty `sets` binds the pipeline's
+    /// own descriptor sets.
+    pub fn dispatch(&self, context: &mut RenderingContext, compute_list: i64, push_constant: &PackedByteArray, sets: &[Rid]) -> Result<(), DispatchError> {
+        self.dispatch_groups(context, compute_list, push_constant, sets, self.block_dimensions)
+    }
+
+    /// Same as `dispatch` with `groups` work groups instead of the pipeline's own.
+    pub fn dispatch_groups(&self, context: &mut RenderingContext, compute_list: i64, push_constant: &PackedByteArray, sets: &[Rid], groups: [u32; 3]) -> Result<(), DispatchError> {
+        if groups.contains(&0) {
+            return Err(DispatchError::EmptyDispatch { groups });
+        }
+        let device = self.bind(context, compute_list, push_constant, sets)?;
+        device.compute_list_dispatch(compute_list, groups[0], groups[1], groups[2]);
+        Ok(())
+    }
+
+    /// Same as `dispatch` with the work groups read from `buffer` at `offset` bytes on the GPU.
+    pub fn dispatch_indirect(&self, context: &mut RenderingContext, compute_list: i64, push_constant: &PackedByteArray, sets: &[Rid], buffer: Rid, offset: u32) -> Result<(), DispatchError> {
+        if !buffer.is_valid() {
+            return Err(DispatchError::InvalidIndirectBuffer);
+        }
+        if !offset.is_multiple_of(4) {
+            return Err(DispatchError::UnalignedIndirectOffset { offset });
+        }
+        let device = self.bind(context, compute_list, push_constant, sets)?;
+        device.compute_list_dispatch_indirect(compute_list, buffer, offset);
+        Ok(())
+    }
+
+    /// Same as `dispatch_groups`, but returns the dispatch as plain data instead of recording it,
+    /// so it can be recorded on another thread with `RecordedDispatch::record`.
+    #[cfg(feature = "simulation-thread")]
+    pub fn record_groups(&self, push_constant: &PackedByteArray, sets: &[Rid], groups: [u32; 3]) -> Result<RecordedDispatch, DispatchError> {
+        if groups.contains(&0) {
+            return Err(DispatchError::EmptyDispatch { groups });
+        }
+        Ok(RecordedDispatch {
+            pipeline: self.pipeline,
+            descriptor_sets: self.sets(sets)?.to_vec(),
+            push_constant: push_constant.to_vec(),
+            groups,
+        })
+    }
+
+    /// Binds the pipeline, push constant and descriptor sets, and returns the device to dispatch on.
+    fn bind<'a>(&self, context: &'a mut RenderingContext, compute_list: i64, push_constant: &PackedByteArray, sets: &[Rid]) -> Result<&'a mut Gd<RenderingDevice>, DispatchError> {
+        let sets = self.sets(sets)?;
+        let device = context.device_mut().ok_or(DispatchError::NoDevice)?;
+        bind_pipeline(device, compute_list, self.pipeline, push_constant, sets);
+        Ok(device)
+    }
+
+    /// `sets`, or the pipeline's own descriptor sets if it is empty.
+    fn sets<'a>(&'a self, sets: &'a [Rid]) -> Result<&'a [Rid], DispatchError> {
+        let sets = if sets.is_empty() { self.descriptor_sets.as_slice() } else { sets };
+        if sets.is_empty() {
+            return Err(DispatchError::NoDescriptorSets);
+        }
+        Ok(sets)
+    }
+}
+
+/// A validated dispatch of a `ComputePipeline` that holds no Godot objects, so it can be sent to
+/// the thread that records it.
+#[cfg(feature = "simulation-thread")]
+#[derive(Clone, Debug)]
+pub(crate) struct RecordedDispatch {
+    pipeline: Rid,
+    descriptor_sets: Vec<Rid>,
+    push_constant: Vec<u8>,
+    groups: [u32; 3],
+}
+
+#[cfg(feature = "simulation-thread")]
+impl RecordedDispatch {
+    /// Records the dispatch into `compute_list` of `device`.
+    pub fn record(&self, device: &mut Gd<RenderingDevice>, compute_list: i64) {
+        let push_constant = PackedByteArray::from(self.push_constant.as_slice());
+        bind_pipeline(device, compute_list, self.pipeline, &push_constant, &self.descriptor_sets);
+        device.compute_list_dispatch(compute_list, self.groups[0], self.groups[1], self.groups[2]);
+    }
+}
+
+fn bind_pipeline(device: &mut Gd<RenderingDevice>, compute_list: i64, pipeline: Rid, push_constant: &PackedByteArray, sets: &[Rid]) {
+    device.compute_list_bind_compute_pipeline(compute_list, pipeline);
+    if !push_constant.is_empty() {
+        device.compute_list_set_push_constant(compute_list, push_constant, push_constant.len() as u32);
+    }
+    for (i, &set) in sets.iter().enumerate() {
+        device.compute_list_bind_uniform_set(compute_list, set, i as u32);
+    }
+}
+
+/// `ComputePipeline` for GDScript, created by `RenderingContext.create_compute_pipeline`. Dispatch
+/// errors are printed and reported as `false`.
+#[derive(GodotClass)]
+#[class(no_init, base=RefCounted)]
+pub struct GdComputePipeline {
+    pipeline: ComputePipeline,
+    context: Gd<RenderingContext>,
+}
+
+impl GdComputePipeline {
+    pub(crate) fn new(pipeline: ComputePipeline, context: Gd<RenderingContext>) -> Gd<Self> {
+        Gd::from_object(Self { pipeline, context })
+    }
+
+    fn report(result: Result<(), DispatchError>) -> bool {
+        result.inspect_err(|e| godot_error!("compute_pipeline.rs: {e}")).is_ok()
+    }
+}
+
+#[godot_api]
+impl GdComputePipeline {
+    /// Records a dispatch into `compute_list`. `groups` overrides the pipeline's work groups when
+    /// it has 3 entries, and `sets` its descriptor sets when it isn't empty.
+    #[func]
+    fn dispatch(&mut self, compute_list: i64, push_constant: PackedByteArray, sets: Array<Rid>, groups: PackedInt32Array) -> bool {
+        let sets: Vec<Rid> = sets.iter_shared().collect();
+        let mut context = self.context.bind_mut();
+        let result = match groups.as_slice() {
+            &[x, y, z] => {
+                let groups = [x, y, z].map(|count| count.max(0) as u32);
+                self.pipeline.dispatch_groups(&mut context, compute_list, &push_constant, &sets, groups)
+            }
+            _ => self.pipeline.dispatch(&mut context, compute_list, &push_constant, &sets),
+        };
+        Self::report(result)
+    }
+
+    /// Records a dispatch into `compute_list` with the work groups read from `buffer` at `offset`.
+    #[func]
+    fn dispatch_indirect(&mut self, compute_list: i64, push_constant: PackedByteArray, sets: Array<Rid>, buffer: Rid, offset: u32) -> bool {
+        let sets: Vec<Rid> = sets.iter_shared().collect();
+        let mut context = self.context.bind_mut();
+        Self::report(self.pipeline.dispatch_indirect(&mut context, compute_list, &push_constant, &sets, buffer, offset))
+    }
+}
@@ -0,0 +1,112 @@
+use godot::prelude::*;
+
+/// CPU-side copy of a single displacement map layer, read back from the GPU so that
+/// gameplay code can query the water surface without touching the rendering device.
+#[derive(Clone)]
+pub(crate) struct DisplacementLayer {
+    map_size: usize,
+    texels: Vec<Vector3>,
+}
+
+impl DisplacementLayer {
+    /// Decodes the raw `R16G16B16A16_SFLOAT` texels returned by `RenderingDevice::texture_get_data`.
+    pub fn from_rgba16f(data: &[u8], map_size: usize) -> Self {
+        let texels = data.chunks_exact(8)
+            .take(map_size * map_size)
+            .map(|texel| Vector3::new(
+                f16_to_f32(u16::from_le_bytes([texel[0], texel[1]])),
+                f16_to_f32(u16::from_le_bytes([texel[2], texel[3]])),
+                f16_to_f32(u16::from_le_bytes([texel[4], texel[5]])),
+            ))
+            .collect();
+        Self { map_size, texels }
+    }
+
+    /// Wraps displacement texels computed on the CPU, e.g. by `ReferenceCascade`.
+    pub fn from_texels(texels: Vec<Vector3>, map_size: usize) -> Self {
+        Self { map_size, texels }
+    }
+
+    /// Bilinearly samples the layer with repeat wrapping, which is what `texture()` does in `water.gdshader`.
+    pub fn sample(&self, uv: Vector2) -> Vector3 {
+        if self.texels.len() < self.map_size * self.map_size || self.map_size == 0 {
+            return Vector3::ZERO;
+        }
+        // Texel centers sit at half-texel offsets
+        let x = uv.x * self.map_size as f32 - 0.5;
+        let y = uv.y * self.map_size as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let texel = |i: i64, j: i64| -> Vector3 {
+            let size = self.map_size as i64;
+            self.texels[(j.rem_euclid(size) * size + i.rem_euclid(size)) as usize]
+        };
+        let (i, j) = (x0 as i64, y0 as i64);
+        let top = texel(i, j).lerp(texel(i + 1, j), fx);
+        let bottom = texel(i, j + 1).lerp(texel(i + 1, j + 1), fx);
+        top.lerp(bottom, fy)
+    }
+}
+
+/// Converts an IEEE 754 half precision float to single precision.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24), // Subnormal
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_floats_decode_exactly() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3555), 1365.0 / 4096.0);
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        // Smallest subnormal and largest subnormal
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x83ff), -1023.0 * 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x8000), 0.0);
+        assert!(f16_to_f32(0x8000).is_sign_negative());
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn rgba16f_texels_skip_alpha() {
+        let data = [0x00, 0x3c, 0x00, 0xc0, 0x00, 0x00, 0x00, 0x3c];
+        let layer = DisplacementLayer::from_rgba16f(&data, 1);
+        assert_eq!(layer.sample(Vector2::new(0.3, 0.7)), Vector3::new(1.0, -2.0, 0.0));
+    }
+
+    #[test]
+    fn sampling_wraps_around_the_edges() {
+        // 2x2 layer with x = 0, 1 in the top row and 2, 3 in the bottom row
+        let texels = (0..4).map(|i| Vector3::new(i as f32, 0.0, 0.0)).collect();
+        let layer = DisplacementLayer::from_texels(texels, 2);
+        // Texel centers
+        assert_eq!(layer.sample(Vector2::new(0.25, 0.25)).x, 0.0);
+        assert_eq!(layer.sample(Vector2::new(0.75, 0.75)).x, 3.0);
+        // Halfway between the texel centers
+        assert_eq!(layer.sample(Vector2::new(0.5, 0.25)).x, 0.5);
+        // The corner lies between the last and first texels in both directions
+        assert_eq!(layer.sample(Vector2::new(0.0, 0.0)).x, 1.5);
+        assert_eq!(layer.sample(Vector2::new(1.0, 0.25)).x, 0.5);
+        // Whole tiles away repeat the same values
+        assert_eq!(layer.sample(Vector2::new(-1.75, 3.25)), layer.sample(Vector2::new(0.25, 0.25)));
+    }
+
+    #[test]
+    fn incomplete_layers_sample_zero() {
+        let layer = DisplacementLayer::from_texels(vec![Vector3::ONE; 3], 2);
+        assert_eq!(layer.sample(Vector2::new(0.5, 0.5)), Vector3::ZERO);
+    }
+}
@@ -0,0 +1,74 @@
+use godot::prelude::*;
+use godot::classes::{Resource, RenderingServer, Texture2DArray};
+
+/// Output of `Ocean::bake`. PNG is not offered since it can't hold the signed, unnormalized
+/// values of the displacement and normal maps.
+#[derive(GodotConvert, Var, Export, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[godot(via = i32)]
+pub enum BakeFormat {
+    /// One half float EXR per map, cascade and frame.
+    Exr = 0,
+    /// A single `OceanFlipbook` resource, saved as `.res` or `.tres`.
+    #[default]
+    Flipbook = 1,
+}
+
+/// Ocean cascades baked by `Ocean::bake`, for targets that can't run the compute pipeline. Every
+/// frame holds one layer per cascade, the same layout `water.gdshader` samples the live maps in.
+#[derive(GodotClass)]
+#[class(base=Resource, tool)]
+pub struct OceanFlipbook {
+    #[export(range = (1.0, 120.0, 1.0, or_greater))]
+    pub frame_rate: real,
+    #[export]
+    pub displacements: Array<Gd<Texture2DArray>>,
+    #[export]
+    pub normals: Array<Gd<Texture2DArray>>,
+    base: Base<Resource>
+}
+
+#[godot_api]
+impl IResource for OceanFlipbook {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            frame_rate: 30.0,
+            displacements: Array::new(),
+            normals: Array::new(),
+            base
+        }
+    }
+}
+
+#[godot_api]
+impl OceanFlipbook {
+    #[func]
+    pub fn get_frame_count(&self) -> i32 {
+        self.displacements.len().min(self.normals.len()) as i32
+    }
+
+    /// Returns the frame shown at `time`. The flipbook loops, so bake it with `Ocean::loop_period`
+    /// set to its duration for a seamless loop.
+    #[func]
+    pub fn get_frame(&self, time: f64) -> i32 {
+        let frame_count = self.get_frame_count().max(1) as i64;
+        ((time * self.frame_rate as f64).floor() as i64).rem_euclid(frame_count) as i32
+    }
+
+    /// Points the global shader parameters `water.gdshader` samples at the frame shown at `time`.
+    #[func]
+    pub fn apply(&self, time: f64) {
+        let frame = self.get_frame(time) as usize;
+        let (Some(displacements), Some(normals)) = (self.displacements.get(frame), self.normals.get(frame)) else {
+            return;
+        };
+        let mut rendering_server = RenderingServer::singleton();
+        rendering_server.global_shader_parameter_set("num_cascades", &(displacements.get_layers() as u32).to_variant());
+        rendering_server.global_shader_parameter_set("displacements", &displacements.to_variant());
+        rendering_server.global_shader_parameter_set("normals", &normals.to_variant());
+    }
+
+    pub(crate) fn push_frame(&mut self, displacements: Gd<Texture2DArray>, normals: Gd<Texture2DArray>) {
+        self.displacements.push(&displacements);
+        self.normals.push(&normals);
+    }
+}
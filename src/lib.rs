@@ -1,8 +1,19 @@
 use godot::prelude::*;
 mod ocean;
+mod buoyancy;
 mod wave_cascade_parameters;
 mod wave_generator;
 mod rendering_context;
+mod compute_pipeline;
+mod displacement_readback;
+mod reference_pipeline;
+mod push_constant;
+mod spectrum;
+mod sea_state;
+mod flipbook;
+mod shader_reflection;
+mod shaders;
+mod simulation_thread;
 struct GDOcean;
 
 #[gdextension]
@@ -1,15 +1,52 @@
-use core::f32;
 use std::io::Error;
 
 use godot::obj::WithBaseField;
 use godot::prelude::*;
-use godot::classes::{Engine, RandomNumberGenerator, RenderingServer, Resource, ShaderMaterial, Texture2DArrayRd, Time};
-use crate::wave_cascade_parameters::WaveCascadeParameters;
-use crate::wave_generator::{WaveGenerator, DESCRIPTOR};
+use godot::register::ConnectHandle;
+use godot::classes::{Engine, RandomNumberGenerator, RenderingServer, Resource, ResourceSaver, ShaderMaterial, Texture2DArray, Texture2DArrayRd, Time};
+use godot::classes::image::Format;
+use godot::classes::Image;
+use godot::global::Error as GodotError;
+use crate::displacement_readback::DisplacementLayer;
+use crate::flipbook::{BakeFormat, OceanFlipbook};
+use crate::reference_pipeline::{ReferenceCascade, UpdateSettings};
+use crate::sea_state::SeaState;
+use crate::shaders;
+use crate::spectrum::{wavenumber_cutoffs, SpectralStatistics, SpectrumSettings};
+use crate::wave_cascade_parameters::{validated_map_size, WaveCascadeParameters};
+use crate::wave_generator::{advance_cascades, cascade_time, WaveGenerator, DESCRIPTOR, G};
+
+/// Number of fixed-point iterations used to undo the horizontal displacement in `get_wave_height`.
+const WAVE_HEIGHT_ITERATIONS: usize = 4;
+
+/// A cascade of the CPU reference pipeline with the displacement of its last update.
+struct ReferenceState {
+    cascade: ReferenceCascade,
+    /// Cascade time and transition progress `displacement` was computed at.
+    updated_at: Option<(f32, f32)>,
+    displacement: DisplacementLayer,
+}
+
+/// A weather change started by `Ocean::transition_to`.
+struct Transition {
+    targets: Array<Option<Gd<WaveCascadeParameters>>>,
+    elapsed: f64,
+    duration: f64,
+}
+
+impl Transition {
+    /// Returns how far the transition has come, from 0 to 1.
+    fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        (self.elapsed / self.duration).clamp(0.0, 1.0) as f32
+    }
+}
 
 #[derive(GodotClass)]
 #[class(tool, base=Node)]
-struct Ocean {
+pub struct Ocean {
     #[export]
     water_material: Option<Gd<ShaderMaterial>>,
     #[export]
@@ -17,18 +54,66 @@ struct Ocean {
     #[export]
     #[var(get = get_parameters, set = set_parameters)]
     parameters: Array<Option<Gd<WaveCascadeParameters>>>,
-    #[export(enum = (_128x128 = 128, _256x256 = 256, _512x512 = 512, _1024x1024 = 1024))]
+    /// Resolution of every cascade's maps. 2048 and 4096 run a slower multi-pass FFT and need
+    /// gigabytes of VRAM, so they are meant for cinematic captures and baking.
+    #[export(enum = (_128x128 = 128, _256x256 = 256, _512x512 = 512, _1024x1024 = 1024, _2048x2048 = 2048, _4096x4096 = 4096))]
     #[var(set = set_map_size, get = get_map_size)]
     map_size: i32,
     #[export(range = (0.0, 60.0, 1.0))]
     #[var(set = set_updates_per_second, get = get_updates_per_second)]
     updates_per_second: real,
+    /// GPU work allowed per ocean update, in cascade map texels. A 512x512 cascade costs 262144.
+    /// Due cascades that don't fit wait for a later update. 0 is unlimited.
+    #[export(range = (0.0, 16777216.0, 1.0, or_greater))]
+    #[var(set = set_update_budget)]
+    update_budget: i64,
+    /// Runs the wave simulation on its own rendering device on a worker thread, so heavy ocean
+    /// updates stay out of the main render graph. The maps are copied to the main device through
+    /// system memory and lag one update behind; an update is skipped while the previous one is
+    /// still running. Needs the extension to be built with the `simulation-thread` feature.
+    #[export]
+    #[var(set = set_simulation_thread)]
+    simulation_thread: bool,
+    /// Shader files to use instead of the shaders built into the library, keyed by shader name,
+    /// e.g. `{"fft_unpack": "res://my_fft_unpack.glsl"}`. Takes effect when the generator is set
+    /// up again.
+    #[export]
+    shader_overrides: Dictionary,
     next_update_time: real,
+    /// Clock that drives every cascade. Peers that agree on it see the same waves.
+    #[var(get = get_ocean_time, set = set_ocean_time)]
+    ocean_time: f64,
+    /// Clock differences above this are snapped instead of slewed, in seconds.
+    #[export(range = (0.0, 5.0, 0.01, or_greater))]
+    time_sync_threshold: real,
+    /// Maximum rate at which the ocean clock runs fast or slow to catch up with the authoritative
+    /// time, in seconds per second.
+    #[export(range = (0.0, 1.0, 0.01))]
+    time_slew_rate: real,
+    time_correction: f64,
+    /// Gravitational acceleration in m/s², shared by every cascade.
+    #[export(range = (0.01, 30.0, 0.01, or_greater))]
+    #[var(get = get_gravity, set = set_gravity)]
+    gravity: real,
+    /// The waves repeat exactly every `loop_period` seconds, for baking looping flipbooks that
+    /// match the live ocean. 0 disables looping.
+    #[export(range = (0.0, 600.0, 0.1, or_greater))]
+    #[var(get = get_loop_period, set = set_loop_period)]
+    loop_period: real,
+    /// Baked cascades shown instead of the live ones, e.g. on targets without compute shaders.
+    #[export]
+    flipbook: Option<Gd<OceanFlipbook>>,
     wave_generator: Option<Gd<WaveGenerator>>,
     rng: Gd<RandomNumberGenerator>,
     time: f32,
     displacement_maps: Gd<Texture2DArrayRd>,
     normal_maps: Gd<Texture2DArrayRd>,
+    displacement_readback: Vec<DisplacementLayer>,
+    readback_frame: Option<u64>,
+    reference_cascades: Vec<ReferenceState>,
+    transition: Option<Transition>,
+    /// Connections to the signals of `parameters`.
+    parameter_connections: Vec<ConnectHandle>,
     params_null: bool,
     initialized: bool,
     base: Base<Node>
@@ -60,12 +145,27 @@ impl INode for Ocean {
             parameters: Array::new(),
             map_size: 1024,
             updates_per_second: 50.0,
+            update_budget: 0,
+            simulation_thread: false,
+            shader_overrides: Dictionary::new(),
             next_update_time: 0.0,
+            ocean_time: 0.0,
+            time_sync_threshold: 0.2,
+            time_slew_rate: 0.1,
+            time_correction: 0.0,
+            gravity: G,
+            loop_period: 0.0,
+            flipbook: None,
             wave_generator: None,
             rng: rng,
             time: 0.0,
             displacement_maps: Texture2DArrayRd::new_gd(),
             normal_maps: Texture2DArrayRd::new_gd(),
+            displacement_readback: Vec::new(),
+            readback_frame: None,
+            reference_cascades: Vec::new(),
+            transition: None,
+            parameter_connections: Vec::new(),
             initialized: false,
             params_null: true,
             base,
@@ -73,6 +173,11 @@ impl INode for Ocean {
     }
     
     fn process(&mut self, delta: f64) {
+        self.advance_ocean_time(delta);
+        if let Some(flipbook) = self.flipbook.as_ref() {
+            flipbook.bind().apply(self.ocean_time);
+            return;
+        }
         // Update waves like the original - check if it's time to update
         if self.updates_per_second == 0.0 || self.time >= self.next_update_time {
             let target_update_delta = 1.0 / (self.updates_per_second + 1e-10);
@@ -100,12 +205,217 @@ impl Ocean {
     pub fn initialize_random(&mut self) {
         self.rng.set_seed(Time::singleton().get_unix_time_from_system().round() as u64);
         self.initialized = true;
+        self.reseed_cascades();
     }
     
     #[func]
     pub fn initialize_set(&mut self, seed: u64) {
         self.rng.set_seed(seed);
         self.initialized = true;
+        self.reseed_cascades();
+    }
+    
+    /// Corrects the ocean clock toward `time` reported by an authoritative source, such as the
+    /// server. Callers should compensate `time` for network latency. Differences above
+    /// `time_sync_threshold` snap immediately; smaller ones are slewed out over the next frames
+    /// so the waves never visibly jump.
+    #[func]
+    pub fn set_authoritative_time(&mut self, time: f64) {
+        let error = time - self.ocean_time;
+        if error.abs() > self.time_sync_threshold as f64 {
+            self.set_ocean_time(time);
+        } else {
+            self.time_correction = error;
+        }
+    }
+    
+    #[func]
+    pub fn get_ocean_time(&self) -> f64 {
+        self.ocean_time
+    }
+    
+    /// Sets the ocean clock immediately, dropping any pending slew.
+    #[func]
+    pub fn set_ocean_time(&mut self, time: f64) {
+        self.ocean_time = time;
+        self.time_correction = 0.0;
+    }
+    
+    #[func]
+    pub fn get_gravity(&self) -> real {
+        self.gravity
+    }
+    
+    /// Sets the gravity of every cascade and regenerates their spectra.
+    #[func]
+    pub fn set_gravity(&mut self, gravity: real) {
+        self.gravity = gravity;
+        for mut param in self.parameters.iter_shared().flatten() {
+            let mut param = param.bind_mut();
+            param.gravity = gravity;
+            param.should_generate_spectrum = true;
+        }
+    }
+    
+    #[func]
+    pub fn get_loop_period(&self) -> real {
+        self.loop_period
+    }
+    
+    /// Sets the loop period of every cascade. Only the dispersion changes, so the spectra are kept.
+    #[func]
+    pub fn set_loop_period(&mut self, loop_period: real) {
+        self.loop_period = loop_period.max(0.0);
+        for (i, mut param) in self.parameters.iter_shared().flatten().enumerate() {
+            let mut param = param.bind_mut();
+            param.loop_period = self.loop_period;
+            param.time = cascade_time(self.ocean_time, i, self.loop_period);
+        }
+    }
+    
+    /// Replaces `parameters` with cascades for a Beaufort force from 0 to 12, so that the
+    /// significant wave height matches the published table.
+    #[func]
+    pub fn apply_sea_state(&mut self, beaufort: i32) {
+        let parameters = SeaState::beaufort(beaufort).cascades(self.gravity);
+        self.set_parameters(parameters);
+    }
+    
+    /// Replaces `parameters` with cascades for a Douglas sea degree from 0 to 9, assuming a fully
+    /// developed sea.
+    #[func]
+    pub fn apply_douglas_sea_state(&mut self, degree: i32) {
+        let parameters = SeaState::douglas(degree, self.gravity).cascades(self.gravity);
+        self.set_parameters(parameters);
+    }
+    
+    /// Blends every cascade's spectrum toward the matching cascade of `target_parameters` over
+    /// `duration` seconds, then makes them the ocean's `parameters`. The targets keep each
+    /// cascade's `spectrum_seed`, `tile_length`, `map_size` and `depth`, so every wave keeps its phase and
+    /// only its amplitude changes. Starting a transition while another is running completes the
+    /// running one first.
+    #[func]
+    pub fn transition_to(&mut self, target_parameters: Array<Option<Gd<WaveCascadeParameters>>>, duration: f64) {
+        if self.params_null || target_parameters.len() != self.parameters.len() {
+            godot_error!("ocean.rs: transition_to needs one target per cascade");
+            return;
+        }
+        for (current, target) in self.parameters.iter_shared().zip(target_parameters.iter_shared()) {
+            let Some(target) = target else {
+                godot_error!("ocean.rs: transition_to needs one target per cascade");
+                return;
+            };
+            if current.as_ref() == Some(&target) {
+                godot_error!("ocean.rs: transition_to needs new parameters, not the current ones");
+                return;
+            }
+        }
+        self.finish_transition();
+        for (current, target) in self.parameters.iter_shared().zip(target_parameters.iter_shared()) {
+            let (Some(current), Some(mut target)) = (current, target) else {
+                continue;
+            };
+            let current = current.bind();
+            let mut target = target.bind_mut();
+            target.spectrum_seed = current.spectrum_seed;
+            target.tile_length = current.tile_length;
+            target.map_size = current.map_size;
+            target.depth = current.depth;
+            target.gravity = self.gravity;
+            target.loop_period = self.loop_period;
+            target.wavenumber_cutoff = current.wavenumber_cutoff;
+            target.should_generate_spectrum = true;
+        }
+        self.transition = Some(Transition { targets: target_parameters, elapsed: 0.0, duration });
+    }
+    
+    #[func]
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_some()
+    }
+    
+    /// Bakes `frame_count` frames of every cascade, starting at ocean time 0, to `path`. EXR frames
+    /// are saved next to `path`, without its extension, as `<path>_displacements_<frame>_<cascade>.exr`
+    /// and `<path>_normals_<frame>_<cascade>.exr`; a flipbook is saved as one `OceanFlipbook` resource.
+    /// Set `loop_period` to `frame_count / frame_rate` beforehand for a seamless loop. Afterwards the
+    /// generator is set up again at the current ocean time, which clears the foam.
+    #[func]
+    pub fn bake(&mut self, path: GString, frame_count: i32, frame_rate: real, format: BakeFormat) -> GodotError {
+        if frame_count <= 0 || frame_rate <= 0.0 || self.params_null {
+            return GodotError::ERR_INVALID_PARAMETER;
+        }
+        if self.wave_generator.is_none() && has_rendering_device() {
+            self.setup_wave_generator();
+        }
+        let Some(mut wave_gen) = self.wave_generator.clone() else {
+            godot_error!("Baking needs a rendering device.");
+            return GodotError::ERR_UNAVAILABLE;
+        };
+        self.finish_transition();
+        let num_cascades = self.parameters.len() as u32;
+        let mut flipbook = OceanFlipbook::new_gd();
+        flipbook.bind_mut().frame_rate = frame_rate;
+        let delta = 1.0 / frame_rate as f64;
+        let mut result = GodotError::OK;
+        let base_path = path.get_basename();
+        for frame in 0..frame_count {
+            let ocean_time = frame as f64 * delta;
+            wave_gen.bind_mut().update_immediately(delta, ocean_time, self.parameters.clone());
+            let displacements = self.bake_images(wave_gen.bind_mut().read_displacement_layers(num_cascades));
+            let normals = self.bake_images(wave_gen.bind_mut().read_normal_layers(num_cascades));
+            match format {
+                BakeFormat::Exr => {
+                    for (cascade, (displacement, normal)) in displacements.iter_shared().zip(normals.iter_shared()).enumerate() {
+                        for (map, image) in [("displacements", displacement), ("normals", normal)] {
+                            let error = image.save_exr(&format!("{base_path}_{map}_{frame:04}_{cascade}.exr"));
+                            if error != GodotError::OK {
+                                result = error;
+                            }
+                        }
+                    }
+                }
+                BakeFormat::Flipbook => {
+                    let mut displacement_array = Texture2DArray::new_gd();
+                    let mut normal_array = Texture2DArray::new_gd();
+                    displacement_array.create_from_images(&displacements);
+                    normal_array.create_from_images(&normals);
+                    flipbook.bind_mut().push_frame(displacement_array, normal_array);
+                }
+            }
+        }
+        if format == BakeFormat::Flipbook {
+            result = ResourceSaver::singleton().save_ex(&flipbook).path(&path).done();
+        }
+        // The generator's clock, foam and maps were left at the last baked frame
+        self.setup_wave_generator();
+        if let Some(wave_gen) = self.wave_generator.as_mut() {
+            wave_gen.bind_mut().update_immediately(0.0, self.ocean_time, self.parameters.clone());
+        }
+        self.readback_frame = None;
+        result
+    }
+    
+    fn bake_images(&self, layers: Vec<PackedByteArray>) -> Array<Gd<Image>> {
+        layers.iter()
+            .filter_map(|data| Image::create_from_data(self.output_map_size(), self.output_map_size(), false, Format::RGBAH, data))
+            .collect()
+    }
+    
+    /// Returns the significant wave height, peak and zero-crossing periods, mean direction and
+    /// steepness of the sea as rendered, each cascade contributing the wavenumbers it covers. Keys
+    /// match `WaveCascadeParameters.get_spectral_statistics`. Integrates the spectra on every call.
+    #[func]
+    pub fn get_spectral_statistics(&self) -> Dictionary {
+        let cascades: Vec<SpectrumSettings> = self.parameters.iter_shared()
+            .flatten()
+            .map(|param| SpectrumSettings::new(&param.bind()))
+            .collect();
+        SpectralStatistics::new(&cascades, true).to_dictionary()
+    }
+    
+    #[func]
+    pub fn get_seed(&self) -> u64 {
+        self.rng.clone().get_seed()
     }
     
     #[func]
@@ -119,6 +429,7 @@ impl Ocean {
             self.wave_generator.as_mut().unwrap().queue_free();
         }
         self.wave_generator = gen;
+        self.readback_frame = None;
         if self.wave_generator != None {
             let gen = self.wave_generator.clone();
             let mut mutself = self.base_mut();
@@ -134,9 +445,35 @@ impl Ocean {
         godot_print!("Updates per second set to {}", self.updates_per_second);
     }
     
+    #[func]
+    pub fn set_update_budget(&mut self, value: i64) {
+        self.update_budget = value.max(0);
+        if let Some(wave_gen) = self.wave_generator.as_mut() {
+            wave_gen.bind_mut().frame_budget = self.update_budget;
+        }
+    }
+    
+    /// Returns which cascades the last update chose and why, see `WaveGenerator::get_last_schedule`.
+    #[func]
+    pub fn get_update_schedule(&self) -> Array<Dictionary> {
+        self.wave_generator.as_ref().map_or(Array::new(), |wave_gen| wave_gen.bind().get_last_schedule())
+    }
+    
+    #[func]
+    pub fn set_simulation_thread(&mut self, value: bool) {
+        if value == self.simulation_thread {
+            return;
+        }
+        self.simulation_thread = value;
+        if self.wave_generator.is_some() {
+            self.setup_wave_generator();
+        }
+    }
+    
     #[func]
     pub fn set_map_size(&mut self, value: i32) {
-        self.map_size = value;
+        self.map_size = validated_map_size("map_size", value);
+        self.split_cascade_bands();
         self.setup_wave_generator();
     }
     
@@ -153,15 +490,14 @@ impl Ocean {
     #[func]
     pub fn set_parameters(&mut self, val: Array<Option<Gd<WaveCascadeParameters>>>) {
         let new_size = val.len();
-        let mut rng = RandomNumberGenerator::new_gd();
         for i in 0..new_size {
             match val.at(i) {
                 Some(mut x) => {
                     let mut param = x.bind_mut();
-                    param.spectrum_seed = Vector2i { x: rng.randi_range(-10000, 10000), y: rng.randi_range(-10000, 10000) };
-                    param.time = 120.0 + f32::consts::PI * (i as f32);
+                    param.loop_period = self.loop_period;
+                    param.time = cascade_time(self.ocean_time, i, self.loop_period);
+                    param.gravity = self.gravity;
                     param.should_generate_spectrum = true; // Ensure spectrum generation
-                    param.signals().scale_changed().connect_other(self, Ocean::scale_changed);
                     self.params_null = false;
                 }
                 None => {
@@ -178,23 +514,244 @@ impl Ocean {
             }
         }
         
+        self.connect_parameter_signals(&val);
         self.parameters = val;
+        self.transition = None;
+        self.reseed_cascades();
+        self.split_cascade_bands();
         self.setup_wave_generator();
         self.update_scales_uniform();
     }
     
+    /// Routes the signals of `parameters` to the ocean, disconnecting the parameters it used before.
+    fn connect_parameter_signals(&mut self, parameters: &Array<Option<Gd<WaveCascadeParameters>>>) {
+        for handle in self.parameter_connections.drain(..) {
+            if handle.is_connected() {
+                handle.disconnect();
+            }
+        }
+        for mut param in parameters.iter_shared().flatten() {
+            let mut param = param.bind_mut();
+            let scale_changed = param.signals().scale_changed().connect_other(self, Ocean::scale_changed);
+            let map_size_changed = param.signals().map_size_changed().connect_other(self, Ocean::cascade_map_size_changed);
+            self.parameter_connections.extend([scale_changed, map_size_changed]);
+        }
+    }
+    
+    /// Advances the ocean clock by `delta`, plus as much of the pending correction toward the
+    /// authoritative time as `time_slew_rate` allows.
+    fn advance_ocean_time(&mut self, delta: f64) {
+        let max_correction = self.time_slew_rate as f64 * delta;
+        let correction = self.time_correction.clamp(-max_correction, max_correction);
+        self.time_correction -= correction;
+        self.ocean_time += delta + correction;
+    }
+    
+    /// Derives every cascade's spectrum seed from the ocean seed and the cascade index, so peers
+    /// given the same seed generate the same ocean.
+    fn reseed_cascades(&mut self) {
+        let seed = self.rng.get_seed();
+        for (i, param) in self.parameters.iter_shared().enumerate() {
+            if let Some(mut param) = param {
+                let mut param = param.bind_mut();
+                param.spectrum_seed = cascade_seed(seed, i as u64);
+                param.should_generate_spectrum = true;
+            }
+        }
+    }
+    
+    /// Gives every cascade its own band of wavenumbers based on its neighbours' tile lengths, so
+    /// waves that fit in several cascades are only added once.
+    fn split_cascade_bands(&mut self) {
+        let cascades: Vec<Gd<WaveCascadeParameters>> = self.parameters.iter_shared().flatten().collect();
+        let tile_lengths: Vec<Vector2> = cascades.iter().map(|param| param.bind().tile_length).collect();
+        let map_sizes: Vec<i32> = cascades.iter().map(|param| self.cascade_map_size(&param.bind())).collect();
+        let cutoffs = wavenumber_cutoffs(&tile_lengths, &map_sizes);
+        for (mut param, cutoff) in cascades.into_iter().zip(cutoffs) {
+            let mut param = param.bind_mut();
+            param.wavenumber_cutoff = cutoff;
+            param.should_generate_spectrum = true;
+        }
+    }
+    
     fn _update_water(&mut self, delta: f64) {
-        if self.wave_generator == None {
-            self.setup_wave_generator();
+        if let Some(transition) = self.transition.as_mut() {
+            transition.elapsed += delta;
         }
-        // Don't return early like the original - continue with update if generator exists
-        if self.wave_generator != None {
-            self.wave_generator.as_mut().unwrap().bind_mut().update(delta, self.parameters.clone());
+        let (targets, progress) = self.transition.as_ref()
+            .map_or((Array::new(), 0.0), |transition| (transition.targets.clone(), transition.progress()));
+        
+        if !has_rendering_device() {
+            // Without a GPU only the cascade clocks advance, and surface queries are answered
+            // by the reference pipeline instead.
+            advance_cascades(&self.parameters, delta, self.ocean_time);
+        } else {
+            if self.wave_generator == None {
+                self.setup_wave_generator();
+            }
+            // Don't return early like the original - continue with update if generator exists
+            if self.wave_generator != None {
+                self.wave_generator.as_mut().unwrap().bind_mut().update(delta, self.ocean_time, self.parameters.clone(), targets, progress);
+            }
+        }
+        
+        if self.transition.is_some() {
+            if progress >= 1.0 {
+                self.finish_transition();
+            }
+            self.update_scales_uniform();
+        }
+    }
+    
+    /// Makes the transition targets the ocean's parameters. Their spectra were already generated
+    /// during the transition, so nothing is regenerated on the GPU.
+    fn finish_transition(&mut self) {
+        let Some(transition) = self.transition.take() else {
+            return;
+        };
+        match self.wave_generator.as_mut() {
+            Some(wave_gen) => wave_gen.bind_mut().finish_transition(),
+            None => {
+                for (i, mut target) in transition.targets.iter_shared().flatten().enumerate() {
+                    let finished = self.reference_cascades.get_mut(i).is_some_and(|state| state.cascade.finish_transition());
+                    if !finished {
+                        target.bind_mut().should_generate_spectrum = true;
+                    }
+                }
+            }
+        }
+        for (i, mut target) in transition.targets.iter_shared().flatten().enumerate() {
+            target.bind_mut().time = cascade_time(self.ocean_time, i, self.loop_period);
         }
+        self.connect_parameter_signals(&transition.targets);
+        self.parameters = transition.targets;
+        self.readback_frame = None;
     }
     
+    /// Returns the summed displacement of every cascade for the undisplaced surface point at
+    /// `world_xz`, using the same `map_scales` as `water.gdshader`. The shader's camera distance
+    /// falloff is not applied.
+    #[func]
+    pub fn get_displacement(&mut self, world_xz: Vector2) -> Vector3 {
+        self.refresh_displacement_readback();
+        self.sample_displacement(&self.get_map_scales(), world_xz)
+    }
+
+    /// Returns the height of the water surface above `world_xz`. Since the waves also move the
+    /// surface horizontally, the displaced point that ends up above `world_xz` is found with a few
+    /// fixed-point iterations before its height is sampled.
+    #[func]
+    pub fn get_wave_height(&mut self, world_xz: Vector2) -> real {
+        self.refresh_displacement_readback();
+        let map_scales = self.get_map_scales();
+        let mut sample_xz = world_xz;
+        for _ in 0..WAVE_HEIGHT_ITERATIONS {
+            let displacement = self.sample_displacement(&map_scales, sample_xz);
+            sample_xz = world_xz - Vector2::new(displacement.x, displacement.z);
+        }
+        self.sample_displacement(&map_scales, sample_xz).y
+    }
+
+    fn sample_displacement(&self, map_scales: &[Vector4], world_xz: Vector2) -> Vector3 {
+        map_scales.iter()
+            .zip(&self.displacement_readback)
+            .fold(Vector3::ZERO, |displacement, (scales, layer)| {
+                displacement + layer.sample(world_xz * Vector2::new(scales.x, scales.y)) * scales.z
+            })
+    }
+
+    /// Reads the displacement maps back from the GPU at most once per frame, and only when a
+    /// query actually needs them.
+    fn refresh_displacement_readback(&mut self) {
+        let frame = Engine::singleton().get_process_frames();
+        if self.readback_frame == Some(frame) {
+            return;
+        }
+        self.readback_frame = Some(frame);
+        let map_size = self.output_map_size() as usize;
+        let num_cascades = self.parameters.len() as u32;
+        self.displacement_readback = match self.wave_generator.as_mut() {
+            Some(wave_gen) => wave_gen.bind_mut()
+                .read_displacement_layers(num_cascades)
+                .iter()
+                .map(|data| DisplacementLayer::from_rgba16f(data.as_slice(), map_size))
+                .collect(),
+            None if !has_rendering_device() => self.evaluate_reference_cascades(),
+            None => Vec::new(),
+        };
+    }
+
+    /// Runs the cascades through the CPU reference pipeline. The spectra are regenerated under the
+    /// same conditions as on the GPU, so the surface matches what a client with a GPU renders.
+    /// A cascade is only evaluated again once its time or the transition has advanced, so foam
+    /// builds up per cascade update rather than per query. This is slow at large map sizes and is
+    /// only meant for machines without a rendering device.
+    fn evaluate_reference_cascades(&mut self) -> Vec<DisplacementLayer> {
+        if self.params_null {
+            return Vec::new();
+        }
+        let progress = self.transition.as_ref().map_or(0.0, Transition::progress);
+        let mut layers = Vec::new();
+        for (i, mut param) in self.parameters.iter_shared().flatten().enumerate() {
+            let mut param = param.bind_mut();
+            let map_size = self.cascade_map_size(&param) as usize;
+            let stale = self.reference_cascades.get(i).is_none_or(|state| state.cascade.map_size() != map_size);
+            if param.should_generate_spectrum || stale {
+                let state = ReferenceState {
+                    cascade: ReferenceCascade::new(map_size, &param),
+                    updated_at: None,
+                    displacement: DisplacementLayer::from_texels(Vec::new(), map_size),
+                };
+                if i < self.reference_cascades.len() {
+                    self.reference_cascades[i] = state;
+                } else {
+                    self.reference_cascades.push(state);
+                }
+                param.should_generate_spectrum = false;
+            }
+            let state = &mut self.reference_cascades[i];
+            if let Some(mut target) = self.transition.as_ref().and_then(|transition| transition.targets.get(i).flatten()) {
+                let mut target = target.bind_mut();
+                if target.should_generate_spectrum {
+                    state.cascade.set_target(&target);
+                    state.updated_at = None;
+                    target.should_generate_spectrum = false;
+                }
+            }
+            let updated_at = Some((param.time, progress));
+            if state.updated_at != updated_at {
+                let maps = state.cascade.update(&UpdateSettings::new(&param), param.time, progress);
+                state.displacement = DisplacementLayer::from_texels(maps.displacement, map_size);
+                state.updated_at = updated_at;
+            }
+            layers.push(state.displacement.clone());
+        }
+        self.reference_cascades.truncate(layers.len());
+        layers
+    }
+
     pub fn scale_changed(&mut self) {
-        // Implementation for scale change handling
+        self.split_cascade_bands();
+        self.update_scales_uniform();
+    }
+
+    fn cascade_map_size_changed(&mut self) {
+        self.split_cascade_bands();
+        self.setup_wave_generator();
+    }
+
+    /// Map size a cascade's spectrum and FFT are computed at.
+    fn cascade_map_size(&self, param: &WaveCascadeParameters) -> i32 {
+        if param.map_size > 0 { param.map_size } else { self.map_size }
+    }
+
+    /// Size of the displacement and normal maps, which fit the largest cascade.
+    fn output_map_size(&self) -> i32 {
+        self.parameters.iter_shared()
+            .flatten()
+            .map(|param| self.cascade_map_size(&param.bind()))
+            .max()
+            .unwrap_or(self.map_size)
     }
     
     fn setup_wave_generator(&mut self) {
@@ -210,15 +767,38 @@ impl Ocean {
                 param.bind_mut().should_generate_spectrum = true;
             }
         }
+        // A new generator also needs the spectra of a running transition
+        if let Some(transition) = self.transition.as_ref() {
+            for mut target in transition.targets.iter_shared().flatten() {
+                target.bind_mut().should_generate_spectrum = true;
+            }
+        }
+        
+        if !has_rendering_device() {
+            return;
+        }
         
         let mut wave_gen_gd = WaveGenerator::new_alloc();
         let do_steps = || -> Result<(), Error> {
             {
                 let mut wave_gen = wave_gen_gd.bind_mut();
                 wave_gen.map_size = self.map_size;
-                wave_gen.init_gpu(2.max(self.parameters.len() as u32));
-                self.displacement_maps.set_texture_rd_rid(wave_gen.descriptors[DESCRIPTOR::DisplacementMap as usize].rid);
-                self.normal_maps.set_texture_rd_rid(wave_gen.descriptors[DESCRIPTOR::NormalMap as usize].rid);
+                wave_gen.frame_budget = self.update_budget;
+                wave_gen.threaded = self.simulation_thread;
+                for name in self.shader_overrides.keys_array().iter_shared() {
+                    if !shaders::ALL.iter().any(|shader| name == shader.name.to_variant()) {
+                        godot_warn!("ocean.rs: There is no shader named {name} to override");
+                    }
+                }
+                wave_gen.shader_overrides = self.shader_overrides.clone();
+                let mut map_sizes: Vec<i32> = self.parameters.iter_shared()
+                    .flatten()
+                    .map(|param| self.cascade_map_size(&param.bind()))
+                    .collect();
+                map_sizes.resize(2.max(map_sizes.len()), self.map_size);
+                wave_gen.init_gpu(PackedInt32Array::from(map_sizes.as_slice())).map_err(Error::other)?;
+                self.displacement_maps.set_texture_rd_rid(wave_gen.displayed_map(DESCRIPTOR::DisplacementMap));
+                self.normal_maps.set_texture_rd_rid(wave_gen.displayed_map(DESCRIPTOR::NormalMap));
                 RenderingServer::singleton().global_shader_parameter_set("num_cascades", &(self.parameters.len() as u32).to_variant());
                 RenderingServer::singleton().global_shader_parameter_set("displacements", &self.displacement_maps.to_variant());
                 RenderingServer::singleton().global_shader_parameter_set("normals", &self.normal_maps.to_variant());
@@ -238,26 +818,68 @@ impl Ocean {
         }
     }
     
-    fn update_scales_uniform(&mut self) {
-        if self.parameters.len() == 0 || self.params_null {
-            return;
+    /// Per-cascade scales packed as `[uv scale, displacement scale, normal scale]`, matching the
+    /// `map_scales` uniform in `water.gdshader`. During a transition the scales are blended toward
+    /// the targets' along with the spectra.
+    fn get_map_scales(&self) -> Vec<Vector4> {
+        if self.params_null {
+            return Vec::new();
         }
-        let mut map_scales: PackedVector4Array = PackedVector4Array::new();
-        map_scales.resize(self.parameters.len());
-        for i in 0..self.parameters.len() {
-            let param = self.parameters.at(i).unwrap();
-            let uv_scale = Vector2::ONE / param.bind().get_tile_length();
-            map_scales[i] = Vector4 { 
+        let map_scale = |param: Gd<WaveCascadeParameters>| {
+            let param = param.bind();
+            let uv_scale = Vector2::ONE / param.get_tile_length();
+            Vector4 { 
                 x: uv_scale.x, 
                 y: uv_scale.y, 
-                z: param.bind().get_displacement_scale(), 
-                w: param.bind().get_normal_scale() 
-            };
+                z: param.get_displacement_scale(), 
+                w: param.get_normal_scale() 
+            }
+        };
+        self.parameters.iter_shared()
+            .flatten()
+            .enumerate()
+            .map(|(i, param)| {
+                let scale = map_scale(param);
+                match self.transition.as_ref() {
+                    Some(transition) => match transition.targets.get(i).flatten() {
+                        Some(target) => scale.lerp(map_scale(target), transition.progress()),
+                        None => scale,
+                    },
+                    None => scale,
+                }
+            })
+            .collect()
+    }
+
+    fn update_scales_uniform(&mut self) {
+        if self.parameters.len() == 0 || self.params_null {
+            return;
         }
+        let map_scales = PackedVector4Array::from(self.get_map_scales().as_slice());
         if self.water_material == None || self.spray_material == None {
             return;
         }
         self.water_material.as_mut().unwrap().set_shader_parameter("map_scales", &map_scales.to_variant());
         self.spray_material.as_mut().unwrap().set_shader_parameter("map_scales", &map_scales.to_variant());
     }
-}
\ No newline at end of file
+}
+
+/// Hashes the ocean seed and a cascade index into a spectrum seed in the range the spectrum
+/// shader expects. Uses SplitMix64 so the result is identical on every platform.
+fn cascade_seed(ocean_seed: u64, cascade_index: u64) -> Vector2i {
+    let mut state = ocean_seed ^ cascade_index.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    Vector2i { x: (next() % 20001) as i32 - 10000, y: (next() % 20001) as i32 - 10000 }
+}
+
+/// Headless exports and dedicated servers run without a rendering device, so nothing can be
+/// dispatched to the GPU.
+fn has_rendering_device() -> bool {
+    RenderingServer::singleton().get_rendering_device().is_some()
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+use godot::prelude::*;
+
+/// Vulkan only guarantees 128 bytes of push constant storage.
+pub(crate) const MAX_PUSH_CONSTANT_SIZE: usize = 128;
+
+/// Builds a push constant block following std430 layout rules, so fields land at the offsets the
+/// `layout(push_constant)` blocks in `shaders/compute` expect. Fields must be pushed in the order
+/// they are declared in the shader.
+#[derive(Default)]
+pub(crate) struct PushConstant {
+    bytes: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum PushConstantError {
+    /// The padded block is larger than `MAX_PUSH_CONSTANT_SIZE`.
+    TooLarge(usize),
+}
+
+impl fmt::Display for PushConstantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushConstantError::TooLarge(size) => write!(f, "Push constant size must be at most {MAX_PUSH_CONSTANT_SIZE} bytes, got {size}"),
+        }
+    }
+}
+
+impl PushConstant {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(dead_code)] // No shader block has a scalar int yet
+    pub fn push_i32(self, value: i32) -> Self {
+        self.push_aligned(4, &value.to_le_bytes())
+    }
+
+    pub fn push_u32(self, value: u32) -> Self {
+        self.push_aligned(4, &value.to_le_bytes())
+    }
+
+    pub fn push_f32(self, value: f32) -> Self {
+        self.push_aligned(4, &value.to_le_bytes())
+    }
+
+    pub fn push_ivec2(self, value: Vector2i) -> Self {
+        self.push_aligned(8, &[value.x.to_le_bytes(), value.y.to_le_bytes()].concat())
+    }
+
+    pub fn push_vec2(self, value: Vector2) -> Self {
+        self.push_aligned(8, &[value.x.to_le_bytes(), value.y.to_le_bytes()].concat())
+    }
+
+    #[allow(dead_code)] // No shader block has a vec4 yet
+    pub fn push_vec4(self, value: Vector4) -> Self {
+        self.push_aligned(16, &[value.x.to_le_bytes(), value.y.to_le_bytes(), value.z.to_le_bytes(), value.w.to_le_bytes()].concat())
+    }
+
+    /// Returns the block padded to a multiple of 16 bytes, or an error if it does not fit in
+    /// `MAX_PUSH_CONSTANT_SIZE`.
+    pub fn finish(mut self) -> Result<Vec<u8>, PushConstantError> {
+        self.pad_to(16);
+        if self.bytes.len() > MAX_PUSH_CONSTANT_SIZE {
+            return Err(PushConstantError::TooLarge(self.bytes.len()));
+        }
+        Ok(self.bytes)
+    }
+
+    /// Same as `finish`, but returns the bytes in the form `RenderingDevice` expects.
+    pub fn into_packed(self) -> Result<PackedByteArray, PushConstantError> {
+        self.finish().map(|bytes| PackedByteArray::from(bytes.as_slice()))
+    }
+
+    fn push_aligned(mut self, alignment: usize, data: &[u8]) -> Self {
+        self.pad_to(alignment);
+        self.bytes.extend_from_slice(data);
+        self
+    }
+
+    fn pad_to(&mut self, alignment: usize) {
+        let padded = self.bytes.len().div_ceil(alignment) * alignment;
+        self.bytes.resize(padded, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_i32(bytes: &[u8], offset: usize) -> i32 {
+        i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn scalars_are_packed_and_padded_to_16_bytes() {
+        let bytes = PushConstant::new()
+            .push_u32(3)
+            .push_f32(0.5)
+            .push_f32(-2.0)
+            .push_i32(-7)
+            .push_u32(u32::MAX)
+            .finish()
+            .unwrap();
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(read_u32(&bytes, 0), 3);
+        assert_eq!(read_f32(&bytes, 4), 0.5);
+        assert_eq!(read_f32(&bytes, 8), -2.0);
+        assert_eq!(read_i32(&bytes, 12), -7);
+        assert_eq!(read_u32(&bytes, 16), u32::MAX);
+        assert!(bytes[20..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn vectors_follow_std430_alignment() {
+        let bytes = PushConstant::new()
+            .push_f32(1.0)
+            .push_vec2(Vector2::new(2.0, 3.0))
+            .push_u32(4)
+            .push_vec4(Vector4::new(5.0, 6.0, 7.0, 8.0))
+            .push_ivec2(Vector2i::new(-9, 10))
+            .finish()
+            .unwrap();
+        assert_eq!(read_f32(&bytes, 0), 1.0);
+        // vec2 aligns to 8 bytes
+        assert_eq!(read_f32(&bytes, 8), 2.0);
+        assert_eq!(read_f32(&bytes, 12), 3.0);
+        assert_eq!(read_u32(&bytes, 16), 4);
+        // vec4 aligns to 16 bytes
+        assert_eq!(read_f32(&bytes, 32), 5.0);
+        assert_eq!(read_f32(&bytes, 44), 8.0);
+        assert_eq!(read_i32(&bytes, 48), -9);
+        assert_eq!(read_i32(&bytes, 52), 10);
+        assert_eq!(bytes.len(), 64);
+    }
+
+    #[test]
+    fn vectors_and_scalars_are_packed_in_order() {
+        let mut push_constant = PushConstant::new()
+            .push_ivec2(Vector2i::new(12, -34))
+            .push_vec2(Vector2::new(50.0, 60.0));
+        for value in 0..8 {
+            push_constant = push_constant.push_f32(value as f32);
+        }
+        let bytes = push_constant.push_u32(2).finish().unwrap();
+        assert_eq!(read_i32(&bytes, 4), -34);
+        assert_eq!(read_f32(&bytes, 12), 60.0);
+        assert_eq!(read_f32(&bytes, 16), 0.0);
+        assert_eq!(read_f32(&bytes, 44), 7.0);
+        assert_eq!(read_u32(&bytes, 48), 2);
+        assert_eq!(bytes.len(), 64);
+    }
+
+    #[test]
+    fn oversized_blocks_are_rejected() {
+        let fits = (0..32).fold(PushConstant::new(), |pc, i| pc.push_u32(i));
+        assert_eq!(fits.finish().map(|bytes| bytes.len()), Ok(MAX_PUSH_CONSTANT_SIZE));
+        let too_large = (0..33).fold(PushConstant::new(), |pc, i| pc.push_u32(i));
+        assert_eq!(too_large.finish(), Err(PushConstantError::TooLarge(144)));
+    }
+
+    #[test]
+    fn empty_block_is_empty() {
+        assert_eq!(PushConstant::new().finish(), Ok(Vec::new()));
+    }
+}
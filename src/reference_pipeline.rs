@@ -0,0 +1,344 @@
+use std::f32::consts::PI;
+use godot::prelude::*;
+use crate::spectrum::SpectrumSettings;
+use crate::wave_cascade_parameters::WaveCascadeParameters;
+
+/// A GPU-free port of the compute pipeline in `shaders/compute`. It runs the same spectrum,
+/// modulation, inverse FFT and unpack stages as `WaveGenerator`, texel for texel, so it can stand
+/// in for the GPU on machines without a rendering device and serve as ground truth for it.
+///
+/// Sources: Jerry Tessendorf - Simulating Ocean Water
+///          Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+pub(crate) struct ReferenceCascade {
+    map_size: usize,
+    /// xy=h0(k), zw=conj(h0(-k)), as written by `spectrum_compute.glsl`
+    spectrum: Vec<Vector4>,
+    /// Spectrum being transitioned to, like the second spectrum layer of each cascade on the GPU.
+    target_spectrum: Option<Vec<Vector4>>,
+    /// Foam persists between updates, like the alpha channel of the normal map.
+    foam: Vec<f32>,
+}
+
+/// Output of one cascade update. All arrays are `map_size * map_size` texels in row-major order,
+/// laid out exactly like the corresponding texture layers.
+pub(crate) struct CascadeMaps {
+    /// Displacement map RGB
+    pub displacement: Vec<Vector3>,
+    /// Normal map RGB: the surface gradient along x and z, then d(hx)/dx
+    pub normal: Vec<Vector3>,
+    /// Normal map A
+    pub foam: Vec<f32>,
+}
+
+/// The parameters of a `WaveCascadeParameters` that `ReferenceCascade::update` reads.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UpdateSettings {
+    pub tile_length: Vector2,
+    pub depth: f32,
+    pub gravity: f32,
+    pub loop_period: f32,
+    pub whitecap: f32,
+    pub foam_grow_rate: f32,
+    pub foam_decay_rate: f32,
+}
+
+impl UpdateSettings {
+    pub fn new(params: &WaveCascadeParameters) -> Self {
+        Self {
+            tile_length: params.tile_length,
+            depth: params.depth,
+            gravity: params.gravity,
+            loop_period: params.loop_period,
+            whitecap: params.whitecap,
+            foam_grow_rate: params.foam_grow_rate,
+            foam_decay_rate: params.foam_decay_rate,
+        }
+    }
+}
+
+impl ReferenceCascade {
+    /// Generates the initial spectrum for `params`, like `spectrum_compute.glsl`. `map_size` must
+    /// be a power of two.
+    pub fn new(map_size: usize, params: &WaveCascadeParameters) -> Self {
+        Self {
+            map_size,
+            spectrum: generate_spectrum(map_size, params),
+            target_spectrum: None,
+            foam: vec![0.0; map_size * map_size],
+        }
+    }
+
+    pub fn map_size(&self) -> usize {
+        self.map_size
+    }
+
+    /// Generates the spectrum that `update` blends toward.
+    pub fn set_target(&mut self, params: &WaveCascadeParameters) {
+        self.target_spectrum = Some(generate_spectrum(self.map_size, params));
+    }
+
+    /// Replaces the spectrum with the target spectrum. Returns false if there was no target.
+    pub fn finish_transition(&mut self) -> bool {
+        match self.target_spectrum.take() {
+            Some(target_spectrum) => {
+                self.spectrum = target_spectrum;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Modulates the spectrum to `time`, transforms it back to the spatial domain and unpacks the
+    /// displacement, normal and foam maps. Foam accumulates across calls using the cascade's
+    /// `whitecap`, `foam_grow_rate` and `foam_decay_rate`. With a target spectrum set, the
+    /// spectrum is first blended toward it by `transition`.
+    pub fn update(&mut self, settings: &UpdateSettings, time: f32, transition: f32) -> CascadeMaps {
+        let mut layers = self.modulate(settings, time, transition);
+        for layer in layers.iter_mut() {
+            // Note: Like the GPU path, there is no second transpose after the column pass.
+            inverse_fft_rows(layer, self.map_size);
+            transpose(layer, self.map_size);
+            inverse_fft_rows(layer, self.map_size);
+        }
+        self.unpack(&layers, settings.whitecap, settings.foam_grow_rate, settings.foam_decay_rate)
+    }
+
+    /// Port of `spectrum_modulate.glsl`. Returns the four packed spectra fed to the inverse FFT.
+    fn modulate(&self, settings: &UpdateSettings, time: f32, transition: f32) -> [Vec<Vector2>; 4] {
+        let n = self.map_size;
+        let (tile_length, depth, gravity, loop_period) = (settings.tile_length, settings.depth, settings.gravity, settings.loop_period);
+        let mut layers: [Vec<Vector2>; 4] = std::array::from_fn(|_| vec![Vector2::ZERO; n * n]);
+        for y in 0..n {
+            for x in 0..n {
+                let i = y * n + x;
+                let k_vec = (Vector2::new(x as f32, y as f32) - Vector2::splat(n as f32 * 0.5)) * 2.0 * PI / tile_length;
+                let k = k_vec.length() + 1e-6;
+                let k_unit = k_vec / k;
+
+                // --- WAVE SPECTRUM MODULATION ---
+                // Both spectra share the same seed, so blending their amplitudes keeps every wave's phase
+                let h0 = match &self.target_spectrum {
+                    Some(target_spectrum) => self.spectrum[i].lerp(target_spectrum[i], transition),
+                    None => self.spectrum[i],
+                };
+                let modulation = exp_complex(dispersion_relation(k, depth, gravity, loop_period) * time);
+                let h = mul_complex(Vector2::new(h0.x, h0.y), modulation) + mul_complex(Vector2::new(h0.z, h0.w), conj_complex(modulation));
+                let h_inv = Vector2::new(-h.y, h.x);
+
+                // --- WAVE DISPLACEMENT CALCULATION ---
+                let hx = h_inv * k_unit.y;
+                let hy = h;
+                let hz = h_inv * k_unit.x;
+
+                // --- WAVE GRADIENT CALCULATION ---
+                let dhy_dx = h_inv * k_vec.y;
+                let dhy_dz = h_inv * k_vec.x;
+                let dhx_dx = -h * k_vec.y * k_unit.y;
+                let dhz_dz = -h * k_vec.x * k_unit.x;
+                let dhz_dx = -h * k_vec.y * k_unit.x;
+
+                // The outputs are all real-valued, so two of them are packed into each complex value.
+                layers[0][i] = pack_real_pair(hx, hy);
+                layers[1][i] = pack_real_pair(hz, dhy_dx);
+                layers[2][i] = pack_real_pair(dhy_dz, dhx_dx);
+                layers[3][i] = pack_real_pair(dhz_dz, dhz_dx);
+            }
+        }
+        layers
+    }
+
+    /// Port of `fft_unpack.glsl`.
+    fn unpack(&mut self, layers: &[Vec<Vector2>; 4], whitecap: f32, foam_grow_rate: f32, foam_decay_rate: f32) -> CascadeMaps {
+        let n = self.map_size;
+        let mut maps = CascadeMaps {
+            displacement: Vec::with_capacity(n * n),
+            normal: Vec::with_capacity(n * n),
+            foam: Vec::with_capacity(n * n),
+        };
+        for y in 0..n {
+            for x in 0..n {
+                let i = y * n + x;
+                // Multiplying output of inverse FFT by below factor is equivalent to ifftshift()
+                let sign_shift = if (x ^ y) & 1 == 0 { 1.0 } else { -1.0 };
+                maps.displacement.push(Vector3::new(layers[0][i].x, layers[0][i].y, layers[1][i].x) * sign_shift);
+
+                let dhy_dx = layers[1][i].y * sign_shift;
+                let dhy_dz = layers[2][i].x * sign_shift;
+                let dhx_dx = layers[2][i].y * sign_shift;
+                let dhz_dz = layers[3][i].x * sign_shift;
+                let dhz_dx = layers[3][i].y * sign_shift;
+
+                let jacobian = (1.0 + dhx_dx) * (1.0 + dhz_dz) - dhz_dx * dhz_dx;
+                let foam_factor = -(jacobian - whitecap).min(0.0);
+                let foam = (self.foam[i] * (-foam_decay_rate).exp() + foam_factor * foam_grow_rate).clamp(0.0, 1.0);
+                self.foam[i] = foam;
+
+                let gradient = Vector2::new(dhy_dx / (1.0 + dhx_dx.abs()), dhy_dz / (1.0 + dhz_dz.abs()));
+                maps.normal.push(Vector3::new(gradient.x, gradient.y, dhx_dx));
+                maps.foam.push(foam);
+            }
+        }
+        maps
+    }
+}
+
+/// Port of `spectrum_compute.glsl`.
+fn generate_spectrum(map_size: usize, params: &WaveCascadeParameters) -> Vec<Vector4> {
+    let settings = SpectrumSettings::new(params);
+    let dims = map_size as i32;
+    let mut spectrum = Vec::with_capacity(map_size * map_size);
+    for y in 0..dims {
+        for x in 0..dims {
+            // We pack the spectra at both k and -k for use in the modulation stage
+            let h0 = settings.amplitude(Vector2i::new(x, y), dims);
+            let h0_neg = conj_complex(settings.amplitude(Vector2i::new((-x).rem_euclid(dims), (-y).rem_euclid(dims)), dims));
+            spectrum.push(Vector4::new(h0.x, h0.y, h0_neg.x, h0_neg.y));
+        }
+    }
+    spectrum
+}
+
+// Source: Jerry Tessendorf - Simulating Ocean Water
+/// Port of `dispersion_relation` in `spectrum_modulate.glsl`.
+fn dispersion_relation(k: f32, depth: f32, gravity: f32, loop_period: f32) -> f32 {
+    let w = (gravity * k * (k * depth).tanh()).sqrt();
+    if loop_period <= 0.0 {
+        return w;
+    }
+    // Quantize to multiples of the loop's fundamental frequency so every wave repeats in sync
+    let w_0 = 2.0 * PI / loop_period;
+    (w / w_0).round() * w_0
+}
+
+// --- HELPER FUNCTIONS ---
+/// Returns exp(j*x).
+fn exp_complex(x: f32) -> Vector2 {
+    Vector2::new(x.cos(), x.sin())
+}
+
+/// Returns (a0 + j*a1)(b0 + j*b1)
+fn mul_complex(a: Vector2, b: Vector2) -> Vector2 {
+    Vector2::new(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x)
+}
+
+fn conj_complex(x: Vector2) -> Vector2 {
+    Vector2::new(x.x, -x.y)
+}
+
+/// Returns a + j*b, which lets two real signals share one inverse FFT.
+fn pack_real_pair(a: Vector2, b: Vector2) -> Vector2 {
+    Vector2::new(a.x - b.y, a.y + b.x)
+}
+
+// --- INVERSE FOURIER TRANSFORM ---
+/// Unnormalized inverse FFT of every row of a `map_size * map_size` grid. Matches the Stockham
+/// kernel in `fft_compute.glsl`, which uses a positive twiddle exponent and no 1/N scaling.
+fn inverse_fft_rows(data: &mut [Vector2], map_size: usize) {
+    for row in data.chunks_exact_mut(map_size) {
+        // Bit-reversal permutation
+        let mut j = 0;
+        for i in 1..map_size {
+            let mut bit = map_size >> 1;
+            while j & bit != 0 {
+                j ^= bit;
+                bit >>= 1;
+            }
+            j |= bit;
+            if i < j {
+                row.swap(i, j);
+            }
+        }
+        let mut len = 2;
+        while len <= map_size {
+            let half = len / 2;
+            for start in (0..map_size).step_by(len) {
+                for k in 0..half {
+                    let twiddle_factor = exp_complex(2.0 * PI * k as f32 / len as f32);
+                    let upper = row[start + k];
+                    let lower = mul_complex(row[start + k + half], twiddle_factor);
+                    row[start + k] = upper + lower;
+                    row[start + k + half] = upper - lower;
+                }
+            }
+            len <<= 1;
+        }
+    }
+}
+
+/// Port of `transpose.glsl`.
+fn transpose(data: &mut [Vector2], map_size: usize) {
+    for y in 0..map_size {
+        for x in (y + 1)..map_size {
+            data.swap(y * map_size + x, x * map_size + y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looping_dispersion_repeats_every_period() {
+        let loop_period = 20.0;
+        for k in [0.01, 0.3, 1.7, 25.0] {
+            let w = dispersion_relation(k, 20.0, 9.81, loop_period);
+            let cycles = w * loop_period / (2.0 * PI);
+            assert!((cycles - cycles.round()).abs() < 1e-4, "k={k} does not repeat, {cycles} cycles per loop");
+        }
+        assert_eq!(dispersion_relation(1.0, 20.0, 9.81, 0.0), (9.81f32 * (20.0f32).tanh()).sqrt());
+    }
+
+    #[test]
+    fn inverse_fft_matches_naive_dft() {
+        let n = 16;
+        let data: Vec<Vector2> = (0..n * 2).map(|i| Vector2::new((i as f32 * 0.37).sin(), (i as f32 * 1.3).cos())).collect();
+        let mut transformed = data.clone();
+        inverse_fft_rows(&mut transformed, n);
+        for (row, output) in data.chunks_exact(n).zip(transformed.chunks_exact(n)) {
+            for (k, &value) in output.iter().enumerate() {
+                let expected = row.iter().enumerate().fold(Vector2::ZERO, |sum, (j, &x)| {
+                    sum + mul_complex(x, exp_complex(2.0 * PI * (j * k) as f32 / n as f32))
+                });
+                assert!((value - expected).length() < 1e-4, "bin {k}: {value:?} != {expected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn single_wave_displaces_like_a_cosine() {
+        let n = 16;
+        let settings = UpdateSettings {
+            tile_length: Vector2::new(50.0, 40.0),
+            depth: 20.0,
+            gravity: 9.81,
+            loop_period: 0.0,
+            whitecap: 0.5,
+            foam_grow_rate: 1.0,
+            foam_decay_rate: 0.1,
+        };
+        // One wave at k and its conjugate at -k, so the surface is real
+        let amplitude = 0.3;
+        let (x, y) = (n / 2 + 2, n / 2 + 1);
+        let mut spectrum = vec![Vector4::ZERO; n * n];
+        spectrum[y * n + x] = Vector4::new(amplitude, 0.0, 0.0, 0.0);
+        spectrum[(n - y) * n + (n - x)] = Vector4::new(0.0, 0.0, amplitude, 0.0);
+        let mut cascade = ReferenceCascade { map_size: n, spectrum, target_spectrum: None, foam: vec![0.0; n * n] };
+
+        let time = 1.7;
+        let maps = cascade.update(&settings, time, 0.0);
+        let k_vec = Vector2::new(x as f32 - n as f32 * 0.5, y as f32 - n as f32 * 0.5) * 2.0 * PI / settings.tile_length;
+        let k_unit = k_vec.normalized();
+        let w = dispersion_relation(k_vec.length(), settings.depth, settings.gravity, settings.loop_period);
+        for row in 0..n {
+            for column in 0..n {
+                // The FFT leaves the x wavenumber along the rows and the y wavenumber along the columns
+                let position = Vector2::new(row as f32, column as f32) * settings.tile_length / n as f32;
+                let phase = k_vec.dot(position) + w * time;
+                let expected = Vector3::new(-k_unit.y * phase.sin(), phase.cos(), -k_unit.x * phase.sin()) * 2.0 * amplitude;
+                let displacement = maps.displacement[row * n + column];
+                assert!((displacement - expected).length() < 1e-4, "texel ({column}, {row}): {displacement:?} != {expected:?}");
+            }
+        }
+    }
+}
@@ -1,16 +1,66 @@
-use std::any::{Any, TypeId};
 use std::collections::HashMap;
+use std::fmt;
 
 use godot::classes::notify::ObjectNotification;
-use godot::classes::rendering_device::{self, DataFormat, TextureType, TextureUsageBits, UniformType};
+use godot::classes::rendering_device::{self, DataFormat, ShaderLanguage, ShaderStage, TextureType, TextureUsageBits, UniformType};
 use godot::prelude::*;
-use godot::classes::{RdShaderFile, RdShaderSpirv, RdTextureFormat, RdTextureView, RdUniform, RenderingDevice, RenderingServer, Resource, ShaderMaterial};
+use godot::classes::{RdShaderFile, RdShaderSource, RdShaderSpirv, RdTextureFormat, RdTextureView, RdUniform, RenderingDevice, RenderingServer, Resource, ShaderMaterial};
+use crate::compute_pipeline::{ComputePipeline, GdComputePipeline};
+use crate::shaders::EmbeddedShader;
+use crate::shader_reflection::{data_format, BindingKind, ShaderReflection};
+
+/// Why `RenderingContext::create_descriptor_set` refused to build a descriptor set. Bindings are
+/// reported as `(set, binding)`.
+#[derive(Debug)]
+pub(crate) enum DescriptorSetError {
+    /// The shader was not loaded through `load_shader`, or its SPIR-V could not be reflected.
+    UnknownShader,
+    /// A descriptor was given for a binding the shader does not declare.
+    UnusedBinding { set: u32, binding: u32 },
+    /// The shader declares a binding no descriptor was given for.
+    MissingBinding { set: u32, binding: u32, name: String },
+    /// The descriptor is a different kind of resource than the shader declares.
+    TypeMismatch { set: u32, binding: u32, name: String, expected: &'static str, found: UniformType },
+    /// A storage image's texture format differs from the format in the shader's layout qualifier.
+    FormatMismatch { set: u32, binding: u32, name: String, expected: DataFormat, found: DataFormat },
+    /// A buffer is smaller than the block the shader declares.
+    BufferTooSmall { set: u32, binding: u32, name: String, min_size: usize, size: usize },
+    /// `RenderingDevice` failed to create the uniform set anyway.
+    CreationFailed { set: u32 },
+}
+
+impl fmt::Display for DescriptorSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DescriptorSetError::UnknownShader => write!(f, "Shader has no reflection data, it must be loaded with load_shader"),
+            DescriptorSetError::UnusedBinding { set, binding } => write!(f, "Shader declares no binding {binding} in set {set}"),
+            DescriptorSetError::MissingBinding { set, binding, name } => write!(f, "No descriptor given for '{name}' (set {set}, binding {binding})"),
+            DescriptorSetError::TypeMismatch { set, binding, name, expected, found } => {
+                write!(f, "'{name}' (set {set}, binding {binding}) is a {expected} in the shader, got {found:?}")
+            }
+            DescriptorSetError::FormatMismatch { set, binding, name, expected, found } => {
+                write!(f, "'{name}' (set {set}, binding {binding}) is declared as {expected:?} in the shader, but the texture is {found:?}")
+            }
+            DescriptorSetError::BufferTooSmall { set, binding, name, min_size, size } => {
+                write!(f, "'{name}' (set {set}, binding {binding}) needs at least {min_size} bytes, the buffer has {size}")
+            }
+            DescriptorSetError::CreationFailed { set } => write!(f, "RenderingDevice failed to create descriptor set {set}"),
+        }
+    }
+}
+
+impl std::error::Error for DescriptorSetError {}
 #[derive(GodotClass)]
 #[class(base=Resource)]
 pub struct RenderingContext {
     device: Option<Gd<RenderingDevice>>,
     deletion_queue: DeletionQueue,
     shader_cache: HashMap<String, Rid>,
+    shader_reflections: HashMap<Rid, ShaderReflection>,
+    /// Whether `device` was created for this context rather than being the main device, which the
+    /// renderer submits and syncs by itself every frame.
+    is_local: bool,
+    /// A submission to the local device may still be running.
     needs_sync: bool,
     base: Base<Resource>
 }
@@ -23,6 +73,8 @@ impl IResource for RenderingContext {
             device: None,
             deletion_queue: DeletionQueue { queue: Array::new() },
             shader_cache: HashMap::new(),
+            shader_reflections: HashMap::new(),
+            is_local: false,
             needs_sync: false,
             base
         }
@@ -32,9 +84,11 @@ impl IResource for RenderingContext {
             ObjectNotification::PREDELETE => {
                 // All resources must be freed
                 // let mut dev = self.device;
+                self.sync();
                 if let Some(held) = self.device.take() {
                     self.deletion_queue.flush(&mut held.clone());
                     self.shader_cache.clear();
+                    self.shader_reflections.clear();
                     let rendering_device = RenderingServer::singleton().get_rendering_device();
                     match rendering_device {
                         Some(render) => {
@@ -81,56 +135,148 @@ impl RenderingContext {
         } else {
             self.device = device;
         }
+        self.is_local = self.device.is_some() && self.device != RenderingServer::singleton().get_rendering_device();
     }
+    /// Starts the work recorded on a local device without waiting for it. Only one submission
+    /// may be in flight, so a pending one is synced first. Does nothing on the main device.
     #[func]
     fn submit(&mut self){
+        if !self.is_local {
+            return;
+        }
+        self.sync();
         self.device.as_mut().unwrap().submit();
         self.needs_sync = true;
     }
+    /// Waits for the last submission to a local device. Does nothing if none is pending.
     #[func]
     fn sync(&mut self){
+        if !self.needs_sync {
+            return;
+        }
         self.device.as_mut().unwrap().sync();
         self.needs_sync = false;
     }
+    /// Begins recording compute work. On a local device this waits for the previous submission,
+    /// which has usually had a whole frame to finish.
     pub fn compute_list_begin(&mut self) -> i64 {
+        self.sync();
         return self.device.as_mut().unwrap().compute_list_begin();
     }
+    /// Ends recording and submits it without waiting. The main device picks the work up with the
+    /// renderer's next frame.
     pub fn compute_list_end(&mut self) {
         self.device.as_mut().unwrap().compute_list_end();
-        self.sync();
         self.submit();
     }
     pub fn compute_list_add_buffer(&mut self, compute_list: i64){
         self.device.as_mut().unwrap().compute_list_add_barrier(compute_list);
     }
+    /// Copies one layer of a texture back to the CPU. The texture must have been created with
+    /// `TextureUsageBits::CAN_COPY_FROM_BIT`. On a local device this waits for pending work first,
+    /// so readback is the only thing that blocks on the GPU.
+    pub fn texture_get_data(&mut self, texture: Rid, layer: u32) -> PackedByteArray {
+        self.sync();
+        self.device.as_mut().expect("Rendering device is none").texture_get_data(texture, layer)
+    }
+    /// Overwrites one layer of a texture created with `TextureUsageBits::CAN_UPDATE_BIT`.
+    #[cfg(feature = "simulation-thread")]
+    pub fn texture_update(&mut self, texture: Rid, layer: u32, data: &PackedByteArray) {
+        self.sync();
+        self.device.as_mut().expect("Rendering device is none").texture_update(texture, layer, data);
+    }
+    /// Overwrites part of a buffer. Must not be called while a compute list is open.
+    pub fn buffer_update(&mut self, buffer: Rid, offset: u32, data: &PackedByteArray) {
+        self.sync();
+        self.device.as_mut().expect("Rendering device is none").buffer_update(buffer, offset, data.len() as u32, data);
+    }
+    /// Loads a compute shader from an `RDShaderFile`, such as an imported `.glsl` file. Files that
+    /// can't be loaded are reported and give an invalid RID.
     #[func]
     pub fn load_shader(&mut self, path: String) -> Rid {
-        if !self.shader_cache.contains_key(path.as_str()){
-            let shader_spirv = load::<RdShaderFile>(path.as_str()).get_spirv().expect(format!("{path} was not a valid shader file, get_spirv failed").as_str());
-            let rid = self.device.as_mut().unwrap().shader_create_from_spirv(&shader_spirv);
-            if(rid == Rid::Invalid){
-                godot_error!("Shader at {path} did not create an RID");
+        self.try_load_shader(&path, &path).unwrap_or(Rid::Invalid)
+    }
+    /// Same as `load_shader`, reporting failures with the shader's `name`.
+    fn try_load_shader(&mut self, name: &str, path: &str) -> Option<Rid> {
+        if let Some(&rid) = self.shader_cache.get(path) {
+            return Some(rid);
+        }
+        let shader_file = match try_load::<RdShaderFile>(path) {
+            Ok(shader_file) => shader_file,
+            Err(e) => {
+                godot_error!("rendering_context.rs: Shader {name} could not be loaded from {path}: {e}");
+                return None;
+            }
+        };
+        let Some(shader_spirv) = shader_file.get_spirv() else {
+            godot_error!("rendering_context.rs: Shader {name} at {path} is not a valid shader file, get_spirv failed");
+            return None;
+        };
+        let rid = self.create_shader(name, shader_spirv);
+        self.shader_cache.insert(path.to_string(), rid);
+        Some(rid)
+    }
+    /// Loads an embedded compute shader, or the shader file at `override_path` instead if given.
+    /// An override that can't be loaded falls back to the embedded shader.
+    pub(crate) fn load_embedded_shader(&mut self, shader: &EmbeddedShader, override_path: Option<String>) -> Rid {
+        if let Some(path) = override_path {
+            if let Some(rid) = self.try_load_shader(shader.name, &path) {
+                return rid;
             }
-            self.deletion_queue.push(rid);
-            self.shader_cache.insert(path.clone(), rid);
+            godot_warn!("rendering_context.rs: Using the embedded {} shader instead", shader.name);
         }
-        return self.shader_cache[path.as_str()];
-
-        // return self.shader_cache.get(path.as_str()).expect("Path was not a valid shaderMaterial or something else went wrong not sure");
+        let key = format!("embedded://{}", shader.name);
+        if !self.shader_cache.contains_key(key.as_str()) {
+            let mut source = RdShaderSource::new_gd();
+            source.set_language(ShaderLanguage::GLSL);
+            source.set_stage_source(ShaderStage::COMPUTE, shader.glsl());
+            let rid = match self.device.as_mut().expect("Rendering device is none").shader_compile_spirv_from_source(&source) {
+                Some(shader_spirv) => self.create_shader(shader.name, shader_spirv),
+                None => {
+                    godot_error!("rendering_context.rs: Shader {} could not be compiled", shader.name);
+                    Rid::Invalid
+                }
+            };
+            self.shader_cache.insert(key.clone(), rid);
+        }
+        self.shader_cache[key.as_str()]
+    }
+    /// Creates a shader from compiled SPIR-V and reflects its bindings. Compile errors are
+    /// reported with the shader's `name`, and give an invalid RID.
+    fn create_shader(&mut self, name: &str, shader_spirv: Gd<RdShaderSpirv>) -> Rid {
+        let compile_error = shader_spirv.get_stage_compile_error(ShaderStage::COMPUTE);
+        if !compile_error.is_empty() {
+            godot_error!("rendering_context.rs: Shader {name} failed to compile:\n{compile_error}");
+            return Rid::Invalid;
+        }
+        let rid = self.device.as_mut().unwrap().shader_create_from_spirv(&shader_spirv);
+        if rid == Rid::Invalid {
+            godot_error!("Shader {name} did not create an RID");
+            return rid;
+        }
+        match ShaderReflection::from_bytes(shader_spirv.get_stage_bytecode(ShaderStage::COMPUTE).as_slice()) {
+            Ok(reflection) => {
+                self.shader_reflections.insert(rid, reflection);
+            }
+            Err(e) => godot_error!("Shader {name} could not be reflected: {e}"),
+        }
+        self.deletion_queue.push(rid);
+        rid
     }
     pub fn create_storage_buffer(&mut self, size: usize, usage: rendering_device::StorageBufferUsage) -> Descriptor {
         let actual_size = size.max(16);
+        let buffer_size = u32::try_from(actual_size).expect("Storage buffer size does not fit in a u32");
         let mut data = PackedByteArray::new();
         data.resize(actual_size);
         data.fill(0); // Initialize with zeros
         
         let mut buffer = self.device.as_mut().expect("Rendering context device is none")
-            .storage_buffer_create_ex(actual_size as u32);
+            .storage_buffer_create_ex(buffer_size);
         buffer = buffer.data(&data);
         buffer = buffer.usage(usage);
         let rid = buffer.done();
         self.deletion_queue.push(rid);
-        Descriptor { rid: rid, descriptor_type: UniformType::STORAGE_BUFFER }
+        Descriptor { rid: rid, descriptor_type: UniformType::STORAGE_BUFFER, format: None, size: actual_size }
     }
     // #[func]
     // pub fn create_storage_buffer(&mut self, mut size: usize, mut data: PackedByteArray, usage: rendering_device::StorageBufferUsage) -> Descriptor {
@@ -154,7 +300,7 @@ impl RenderingContext {
         buffer = buffer.data(&data);
         let rid = buffer.done();
         self.deletion_queue.push(rid);
-        Descriptor { rid: rid, descriptor_type: UniformType::UNIFORM_BUFFER }
+        Descriptor { rid: rid, descriptor_type: UniformType::UNIFORM_BUFFER, format: None, size: size.max(data.len()) }
     }
     pub fn create_texture(&mut self, dimensions: Vector2i, format: DataFormat, usage: TextureUsageBits, mut num_layers: u32, view: Gd<RdTextureView>, data: Array<PackedByteArray>) -> Descriptor{
         if num_layers < 1{
@@ -175,179 +321,109 @@ impl RenderingContext {
         let rid = texture.data(&data).done();
         self.deletion_queue.push(rid);
         // godot_print!("Finished creating texture");
-        Descriptor { rid: rid, descriptor_type: UniformType::IMAGE }
+        Descriptor { rid: rid, descriptor_type: UniformType::IMAGE, format: Some(format), size: 0 }
     }
     // ## Creates a descriptor set. The ordering of the provided descriptors matches the binding ordering
     // ## within the shader.
     // Seemingly the vector of descriptors was unnecessary so it is now a single descriptor instead
-    pub fn create_descriptor_set(&mut self, descriptor:&Descriptor, shader: Rid, descriptor_set_index: u32) -> Rid{
-        let mut uniforms: Array<Gd<RdUniform>> = Array::new();
-        // for i in 0..descriptors.len() {
-        let mut uniform = RdUniform::new_gd();
-        uniform.set_uniform_type(descriptor.descriptor_type);
-        uniform.set_binding(0 as i32);
-        uniform.add_id(descriptor.rid);
-        uniforms.push(&uniform);
-        // }
-        let rid = self.device.as_mut().expect("Rendering device is none").uniform_set_create(&uniforms, shader, descriptor_set_index);
-        self.deletion_queue.push(rid);
-        return rid;
+    pub fn create_descriptor_set(&mut self, descriptor:&Descriptor, shader: Rid, descriptor_set_index: u32) -> Result<Rid, DescriptorSetError> {
+        self.create_uniform_set(&[descriptor], shader, descriptor_set_index)
+    }
+    pub fn create_descriptor_set_dual(&mut self, descriptor:&Descriptor, descriptor2: &Descriptor, shader: Rid, descriptor_set_index: u32) -> Result<Rid, DescriptorSetError> {
+        self.create_uniform_set(&[descriptor, descriptor2], shader, descriptor_set_index)
     }
-    pub fn create_descriptor_set_dual(&mut self, descriptor:&Descriptor, descriptor2: &Descriptor, shader: Rid, descriptor_set_index: u32) -> Rid{
+    /// Binds `descriptors` to bindings 0, 1, ... of a descriptor set, after checking them against
+    /// the bindings reflected from the shader's SPIR-V.
+    fn create_uniform_set(&mut self, descriptors: &[&Descriptor], shader: Rid, descriptor_set_index: u32) -> Result<Rid, DescriptorSetError> {
+        self.validate_descriptor_set(descriptors, shader, descriptor_set_index)?;
         let mut uniforms: Array<Gd<RdUniform>> = Array::new();
-        // for i in 0..descriptors.len() {
-        let mut uniform = RdUniform::new_gd();
-        uniform.set_uniform_type(descriptor.descriptor_type);
-        uniform.set_binding(0 as i32);
-        uniform.add_id(descriptor.rid);
-        uniforms.push(&uniform);
-        let mut uniform = RdUniform::new_gd();
-        uniform.set_uniform_type(descriptor2.descriptor_type);
-        uniform.set_binding(1 as i32);
-        uniform.add_id(descriptor2.rid);
-        uniforms.push(&uniform);
-        // }
+        for (binding, descriptor) in descriptors.iter().enumerate() {
+            let mut uniform = RdUniform::new_gd();
+            uniform.set_uniform_type(descriptor.descriptor_type);
+            uniform.set_binding(binding as i32);
+            uniform.add_id(descriptor.rid);
+            uniforms.push(&uniform);
+        }
         let rid = self.device.as_mut().expect("Rendering device is none").uniform_set_create(&uniforms, shader, descriptor_set_index);
+        if !rid.is_valid() {
+            return Err(DescriptorSetError::CreationFailed { set: descriptor_set_index });
+        }
         self.deletion_queue.push(rid);
-        return rid;
+        Ok(rid)
     }
-    pub fn create_pipeline(
-        &mut self, 
-        block_dimensions: Vec<i32>, 
-        descriptor_sets: Vec<Rid>, 
-        shader: Rid
-    ) -> Callable {
-        // Create the pipeline using your deletion queue and device
-        let pipeline = self.device.as_mut().expect("Rendering device is none").compute_pipeline_create(shader);
-        self.deletion_queue.push(pipeline);
-        
-        
-        // Create and return the Callable
-        Callable::from_local_fn("pipeline_execute", move |args: &[&Variant]| -> Result<Variant, ()> {
-            // godot_print!("Inside pipeline");
-            // Extract arguments from the Variant array
-            let arg_one = args.get(0).unwrap();
-            // godot_print!("Arg one type: {:?}", arg_one.get_type());
-            let mut context: Gd<RenderingContext> = arg_one.try_to().expect("First argument must be a Gd<RenderingContext>");
-            // let mut context = args.get(0)
-            //     .and_then(|v| v.try_to::<Gd<RenderingContext>>().ok())
-            //     .expect("First argument must be RenderingContext");
-            let arg_two = args.get(1).unwrap();
-            let compute_list = arg_two.try_to().expect("Arg two is an i64");
-            // let compute_list = args.get(1)
-            //     .and_then(|v| v.try_to::<i64>().ok())
-            //     .unwrap_or(0);
-            
-            let push_constant = args.get(2)
-                .and_then(|v| v.try_to::<PackedByteArray>().ok())
-                .unwrap_or_else(|| PackedByteArray::new());
-            
-            let descriptor_set_overwrites: Vec<Rid> = args.get(3)
-                .and_then(|v| v.try_to::<VariantArray>().ok())
-                .map(|arr| {
-                    (0..arr.len())
-                        .filter_map(|i| arr.at(i).try_to::<Rid>().ok())
-                        .collect()
-                })
-                .unwrap_or_default();
-            
-            let block_dimensions_overwrite_buffer = args.get(4)
-                .and_then(|v| v.try_to::<Rid>().ok());
-            
-            let block_dimensions_overwrite_buffer_byte_offset = args.get(5)
-                .and_then(|v| v.try_to::<i64>().ok())
-                .unwrap_or(0);
-            
-            // Execute the pipeline logic
-            let mut context_bind = context.bind_mut();
-
-            let mut device = context_bind.device.as_mut().expect("Rendering device was none");
-            
-            let sets = if descriptor_set_overwrites.is_empty() {
-                &descriptor_sets
-            } else {
-                &descriptor_set_overwrites
+    fn validate_descriptor_set(&self, descriptors: &[&Descriptor], shader: Rid, set: u32) -> Result<(), DescriptorSetError> {
+        let reflection = self.shader_reflections.get(&shader).ok_or(DescriptorSetError::UnknownShader)?;
+        for (binding, descriptor) in descriptors.iter().enumerate() {
+            let binding = binding as u32;
+            let declared = reflection.binding(set, binding).ok_or(DescriptorSetError::UnusedBinding { set, binding })?;
+            let name = declared.name.clone();
+            let type_matches = match declared.kind {
+                BindingKind::StorageImage { .. } => descriptor.descriptor_type == UniformType::IMAGE,
+                BindingKind::Texture => [UniformType::TEXTURE, UniformType::SAMPLER_WITH_TEXTURE, UniformType::SAMPLER].contains(&descriptor.descriptor_type),
+                BindingKind::StorageBuffer { .. } => descriptor.descriptor_type == UniformType::STORAGE_BUFFER,
+                BindingKind::UniformBuffer { .. } => descriptor.descriptor_type == UniformType::UNIFORM_BUFFER,
             };
-            
-            // Assertions
-            assert!(
-                block_dimensions.len() == 3 || block_dimensions_overwrite_buffer.is_some(),
-                "Must specify block dimensions or specify a dispatch indirect buffer!"
-            );
-            assert!(sets.len() >= 1, "Must specify at least one descriptor set!");
-            
-            // Bind pipeline and set push constants
-            device.compute_list_bind_compute_pipeline(compute_list, pipeline);
-            // godot_print!("Setting push constant");
-            device.compute_list_set_push_constant(
-                compute_list, 
-                &push_constant, 
-                push_constant.len() as u32
-            );
-            
-            // Bind uniform sets
-            for (i, &set) in sets.iter().enumerate() {
-                device.compute_list_bind_uniform_set(compute_list, set, i as u32);
+            if !type_matches {
+                return Err(DescriptorSetError::TypeMismatch { set, binding, name, expected: declared.kind.name(), found: descriptor.descriptor_type });
             }
-            
-            // Dispatch
-            if let Some(buffer) = block_dimensions_overwrite_buffer {
-                device.compute_list_dispatch_indirect(
-                    compute_list, 
-                    buffer, 
-                    block_dimensions_overwrite_buffer_byte_offset as u32
-                );
-            } else {
-                device.compute_list_dispatch(
-                    compute_list,
-                    block_dimensions[0] as u32,
-                    block_dimensions[1] as u32,
-                    block_dimensions[2] as u32,
-                );
+            match declared.kind {
+                BindingKind::StorageImage { format } => {
+                    if let (Some(expected), Some(found)) = (data_format(format), descriptor.format) {
+                        if expected != found {
+                            return Err(DescriptorSetError::FormatMismatch { set, binding, name, expected, found });
+                        }
+                    }
+                }
+                BindingKind::StorageBuffer { min_size } | BindingKind::UniformBuffer { min_size } => {
+                    if descriptor.size < min_size {
+                        return Err(DescriptorSetError::BufferTooSmall { set, binding, name, min_size, size: descriptor.size });
+                    }
+                }
+                BindingKind::Texture => {}
             }
-            
-            // Return void (empty Variant)
-            Ok(Variant::nil())
-        })
-    }
-    // ## Returns a [PackedFloat32Array] from the provided data, whose size is rounded up to the nearest
-    // ## multiple of 16
-    pub fn create_push_constant(data : &[Variant]) -> PackedByteArray{
-        let packed_size: i32 = (data.len() * 4) as i32;
-        if packed_size > 128{
-            panic!("Push constant size must be at most 128 bytes!");
-        } else {
-            // godot_print!("Creating push constant with size {packed_size}");
         }
-
-        let padding = (packed_size as f32 / 16.0).ceil() as i32 * 16 - packed_size;
-        let mut packed_data = PackedByteArray::new();
-        if padding > 0 {
-            packed_data.resize((packed_size + padding) as usize);
-        } else {
-            packed_data.resize(packed_size as usize);
+        if let Some(missing) = reflection.bindings_in_set(set).find(|declared| declared.binding as usize >= descriptors.len()) {
+            return Err(DescriptorSetError::MissingBinding { set, binding: missing.binding, name: missing.name.clone() });
         }
-        packed_data.fill(0);
-        for i in 0..data.len() {
-            let d = &data[i];
-            if d.type_id() == TypeId::of::<i32>(){
-                _ = packed_data.encode_s32(i * 4, d.to());
-            } else if d.type_id() == TypeId::of::<f32>(){
-                _ = packed_data.encode_float(i * 4, d.to());
+        Ok(())
+    }
+    /// Creates a compute pipeline that dispatches `block_dimensions` work groups with
+    /// `descriptor_sets` bound by default.
+    pub(crate) fn create_pipeline(&mut self, block_dimensions: [u32; 3], descriptor_sets: Vec<Rid>, shader: Rid) -> ComputePipeline {
+        let pipeline = self.device.as_mut().expect("Rendering device is none").compute_pipeline_create(shader);
+        self.deletion_queue.push(pipeline);
+        ComputePipeline::new(pipeline, block_dimensions, descriptor_sets)
+    }
+    /// Same as `create_pipeline`, wrapped for GDScript.
+    #[func]
+    fn create_compute_pipeline(&mut self, block_dimensions: PackedInt32Array, descriptor_sets: Array<Rid>, shader: Rid) -> Gd<GdComputePipeline> {
+        let block_dimensions = match block_dimensions.as_slice() {
+            &[x, y, z] => [x, y, z].map(|count| count.max(0) as u32),
+            _ => {
+                godot_error!("rendering_context.rs: Block dimensions need 3 entries, using [1, 1, 1]");
+                [1, 1, 1]
             }
-        }
-        return packed_data
+        };
+        let pipeline = self.create_pipeline(block_dimensions, descriptor_sets.iter_shared().collect(), shader);
+        GdComputePipeline::new(pipeline, self.to_gd())
+    }
+    pub(crate) fn device_mut(&mut self) -> Option<&mut Gd<RenderingDevice>> {
+        self.device.as_mut()
     }
 }
 #[derive(GodotClass)]
 #[class(no_init)]
 pub struct Descriptor {
     pub rid: Rid,
-    pub descriptor_type: UniformType
+    pub descriptor_type: UniformType,
+    /// Texture format of images, checked against the shader's layout qualifier.
+    pub format: Option<DataFormat>,
+    /// Size of buffers in bytes.
+    pub size: usize,
 }
 impl Default for Descriptor {
     fn default() -> Self {
-        Self { rid: Rid::Invalid, descriptor_type: UniformType::STORAGE_BUFFER }
+        Self { rid: Rid::Invalid, descriptor_type: UniformType::STORAGE_BUFFER, format: None, size: 0 }
     }
 }
 struct DeletionQueue {
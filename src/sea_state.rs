@@ -0,0 +1,182 @@
+use std::f32::consts::PI;
+use godot::prelude::*;
+use crate::spectrum::SpectrumSettings;
+use crate::wave_cascade_parameters::{SpectrumModel, WaveCascadeParameters};
+use crate::wave_generator::jonswap_peak_angular_frequency;
+
+/// Number of cascades a sea state is split into.
+const NUM_CASCADES: usize = 3;
+/// Ratio between the tile lengths of neighbouring cascades.
+const CASCADE_TILE_RATIO: f32 = 4.0;
+/// The largest cascade fits this many peak wavelengths.
+const PEAK_WAVELENGTHS_PER_TILE: f32 = 4.0;
+/// Range searched for a fetch that produces the wanted wave height, in km.
+const FETCH_RANGE: (f32, f32) = (0.01, 1e5);
+const FETCH_SEARCH_ITERATIONS: usize = 40;
+/// Slowest wind a sea state uses, since the spectra divide by the wind speed.
+const MIN_WIND_SPEED: f32 = 0.2;
+/// Tile length of the largest cascade of a calm sea, which has no peak wavelength to follow.
+const CALM_TILE_LENGTH: f32 = 50.0;
+
+/// Conditions of a published sea state, from which a physically consistent set of cascades is built.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SeaState {
+    /// Wind speed at 10 m in m/s
+    pub wind_speed: f32,
+    /// Significant wave height in m
+    pub significant_wave_height: f32,
+    pub whitecap: f32,
+    pub foam_amount: f32,
+    pub swell: f32,
+}
+
+const fn sea_state(wind_speed: f32, significant_wave_height: f32, whitecap: f32, foam_amount: f32, swell: f32) -> SeaState {
+    SeaState { wind_speed, significant_wave_height, whitecap, foam_amount, swell }
+}
+
+/// Mid-range wind speed and probable wave height of every Beaufort force. Whitecaps appear at
+/// force 3 and spread into streaks of foam from force 7; wind seas grow short-crested as they build.
+// Source: WMO - Manual on Marine Meteorological Services, Beaufort wind scale
+const BEAUFORT_SCALE: [SeaState; 13] = [
+    sea_state(MIN_WIND_SPEED, 0.0, 0.0, 0.0, 1.0),
+    sea_state(0.9, 0.1, 0.0, 0.0, 1.0),
+    sea_state(2.5, 0.2, 0.0, 0.0, 1.0),
+    sea_state(4.4, 0.6, 0.3, 1.0, 0.9),
+    sea_state(6.7, 1.0, 0.45, 2.0, 0.9),
+    sea_state(9.4, 2.0, 0.6, 3.5, 0.8),
+    sea_state(12.3, 3.0, 0.7, 5.0, 0.8),
+    sea_state(15.5, 4.0, 0.8, 6.0, 0.7),
+    sea_state(18.9, 5.5, 0.9, 7.0, 0.7),
+    sea_state(22.6, 7.0, 1.0, 8.0, 0.6),
+    sea_state(26.4, 9.0, 1.1, 9.0, 0.6),
+    sea_state(30.5, 11.5, 1.2, 9.5, 0.5),
+    sea_state(34.0, 14.0, 1.3, 10.0, 0.5),
+];
+
+/// Mid-range significant wave height of every Douglas sea degree, in m.
+// Source: WMO - Manual on Marine Meteorological Services, Douglas sea scale
+const DOUGLAS_SEA_SCALE: [f32; 10] = [0.0, 0.05, 0.3, 0.9, 1.9, 3.25, 5.0, 7.5, 11.5, 14.0];
+
+impl SeaState {
+    /// Returns the sea state of a Beaufort force, clamped to 0-12.
+    pub fn beaufort(force: i32) -> Self {
+        BEAUFORT_SCALE[force.clamp(0, 12) as usize]
+    }
+
+    /// Returns the sea state of a Douglas sea degree, clamped to 0-9. The wind speed is the one
+    /// that fully develops a sea of that height, and the foam follows the closest Beaufort force.
+    // Source: https://wikiwaves.org/Ocean-Wave_Spectra#Pierson-Moskowitz_Spectrum (H_s = 0.21 U²/g)
+    pub fn douglas(degree: i32, gravity: f32) -> Self {
+        let significant_wave_height = DOUGLAS_SEA_SCALE[degree.clamp(0, 9) as usize];
+        let closest = BEAUFORT_SCALE.iter()
+            .min_by(|a, b| {
+                let a = (a.significant_wave_height - significant_wave_height).abs();
+                let b = (b.significant_wave_height - significant_wave_height).abs();
+                a.total_cmp(&b)
+            })
+            .copied()
+            .unwrap_or(BEAUFORT_SCALE[0]);
+        Self {
+            wind_speed: (significant_wave_height * gravity / 0.21).sqrt().max(MIN_WIND_SPEED),
+            significant_wave_height,
+            ..closest
+        }
+    }
+
+    /// Builds the cascades for this sea state. The fetch is chosen so that the JONSWAP spectrum of
+    /// the wind reaches the sea state's significant wave height, and the tile lengths follow the
+    /// resulting peak wavelength. A calm sea keeps the shortest fetch and `CALM_TILE_LENGTH`, since
+    /// its peak wavelength would shrink the tiles to millimetres.
+    pub fn cascades(&self, gravity: f32) -> Array<Option<Gd<WaveCascadeParameters>>> {
+        let fetch_length = self.fetch_length(gravity);
+        let mut tile_length = if self.is_calm() {
+            CALM_TILE_LENGTH
+        } else {
+            let peak_frequency = jonswap_peak_angular_frequency(self.wind_speed, fetch_length * 1e3, gravity);
+            let peak_wavelength = 2.0 * PI * gravity / (peak_frequency * peak_frequency);
+            peak_wavelength * PEAK_WAVELENGTHS_PER_TILE
+        };
+        (0..NUM_CASCADES)
+            .map(|_| {
+                let mut params = self.cascade(fetch_length, gravity);
+                params.bind_mut().tile_length = Vector2::splat(tile_length);
+                tile_length /= CASCADE_TILE_RATIO;
+                Some(params)
+            })
+            .collect()
+    }
+
+    fn cascade(&self, fetch_length: f32, gravity: f32) -> Gd<WaveCascadeParameters> {
+        let mut params = WaveCascadeParameters::new_gd();
+        {
+            let mut param = params.bind_mut();
+            param.spectrum_model = SpectrumModel::Jonswap;
+            param.wind_speed = self.wind_speed;
+            param.fetch_length = fetch_length;
+            param.gravity = gravity;
+            param.whitecap = self.whitecap;
+            param.foam_amount = self.foam_amount;
+            param.swell = self.swell;
+        }
+        params
+    }
+
+    /// Whether the sea is flat, like Beaufort force 0.
+    fn is_calm(&self) -> bool {
+        self.significant_wave_height <= 0.0
+    }
+
+    /// Bisects for the fetch, in km, whose spectrum has the wanted significant wave height. The
+    /// height grows monotonically with fetch, so the search runs in log space.
+    fn fetch_length(&self, gravity: f32) -> f32 {
+        if self.is_calm() {
+            return FETCH_RANGE.0;
+        }
+        let (mut low, mut high) = (FETCH_RANGE.0.ln(), FETCH_RANGE.1.ln());
+        for _ in 0..FETCH_SEARCH_ITERATIONS {
+            let mid = 0.5 * (low + high);
+            if SpectrumSettings::jonswap(self.wind_speed, mid.exp(), gravity).significant_wave_height() < self.significant_wave_height {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        (0.5 * (low + high)).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wave_generator::G;
+
+    fn assert_reproduces_significant_wave_height(sea_state: SeaState) {
+        let fetch_length = sea_state.fetch_length(G);
+        let significant_wave_height = SpectrumSettings::jonswap(sea_state.wind_speed, fetch_length, G).significant_wave_height();
+        assert!(
+            (significant_wave_height / sea_state.significant_wave_height - 1.0).abs() < 1e-2,
+            "{sea_state:?} reached H_s = {significant_wave_height} with a fetch of {fetch_length} km"
+        );
+    }
+
+    #[test]
+    fn beaufort_forces_reproduce_their_wave_height() {
+        for force in 1..=12 {
+            assert_reproduces_significant_wave_height(SeaState::beaufort(force));
+        }
+    }
+
+    #[test]
+    fn douglas_degrees_reproduce_their_wave_height() {
+        for degree in 1..=9 {
+            assert_reproduces_significant_wave_height(SeaState::douglas(degree, G));
+        }
+    }
+
+    #[test]
+    fn calm_seas_keep_the_shortest_fetch() {
+        assert!(SeaState::beaufort(0).is_calm());
+        assert!(SeaState::douglas(0, G).is_calm());
+        assert_eq!(SeaState::beaufort(0).fetch_length(G), FETCH_RANGE.0);
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::fmt;
+use godot::classes::rendering_device::DataFormat;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+// Opcodes
+const OP_NAME: u32 = 5;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+// Decorations
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// Storage classes
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+/// What a shader expects at a binding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BindingKind {
+    /// An `image*` uniform. `format` is the SPIR-V image format of its layout qualifier.
+    StorageImage { format: u32 },
+    /// A `sampler*` or `texture*` uniform.
+    Texture,
+    /// A `buffer` block that needs at least `min_size` bytes.
+    StorageBuffer { min_size: usize },
+    /// A `uniform` block that needs at least `min_size` bytes.
+    UniformBuffer { min_size: usize },
+}
+
+impl BindingKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BindingKind::StorageImage { .. } => "storage image",
+            BindingKind::Texture => "texture",
+            BindingKind::StorageBuffer { .. } => "storage buffer",
+            BindingKind::UniformBuffer { .. } => "uniform buffer",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ShaderBinding {
+    pub set: u32,
+    pub binding: u32,
+    /// Name of the uniform in the shader, if the SPIR-V kept it.
+    pub name: String,
+    pub kind: BindingKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum ReflectionError {
+    /// The bytecode does not start with the SPIR-V magic number.
+    NotSpirv,
+    /// An instruction runs past the end of the bytecode.
+    Truncated,
+}
+
+impl fmt::Display for ReflectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReflectionError::NotSpirv => write!(f, "Bytecode is not SPIR-V"),
+            ReflectionError::Truncated => write!(f, "SPIR-V bytecode is truncated"),
+        }
+    }
+}
+
+/// Descriptor bindings declared by a SPIR-V module, read straight from its decorations so that
+/// descriptor sets can be checked before `RenderingDevice` silently accepts a mismatch.
+#[derive(Debug, Default)]
+pub(crate) struct ShaderReflection {
+    bindings: Vec<ShaderBinding>,
+}
+
+#[derive(Clone, Debug)]
+enum Type {
+    Scalar { size: usize },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { sampled: u32, format: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray,
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+}
+
+impl ShaderReflection {
+    /// Reflects little-endian SPIR-V bytecode, as returned by `RdShaderSpirv::get_stage_bytecode`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReflectionError> {
+        let words: Vec<u32> = bytes.chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        Self::from_words(&words)
+    }
+
+    pub fn from_words(words: &[u32]) -> Result<Self, ReflectionError> {
+        if words.len() < HEADER_WORDS || words[0] != SPIRV_MAGIC {
+            return Err(ReflectionError::NotSpirv);
+        }
+        let mut names: HashMap<u32, String> = HashMap::new();
+        let mut types: HashMap<u32, Type> = HashMap::new();
+        let mut constants: HashMap<u32, u32> = HashMap::new();
+        let mut decorations: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut member_offsets: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut variables: Vec<(u32, u32, u32)> = Vec::new(); // (id, pointer type, storage class)
+
+        let mut i = HEADER_WORDS;
+        while i < words.len() {
+            let word_count = (words[i] >> 16) as usize;
+            let opcode = words[i] & 0xffff;
+            if word_count == 0 || i + word_count > words.len() {
+                return Err(ReflectionError::Truncated);
+            }
+            let operands = &words[i + 1..i + word_count];
+            let operand = |index: usize| operands.get(index).copied().unwrap_or(0);
+            match opcode {
+                OP_NAME => {
+                    names.insert(operand(0), literal_string(&operands[1.min(operands.len())..]));
+                }
+                OP_TYPE_INT | OP_TYPE_FLOAT => {
+                    types.insert(operand(0), Type::Scalar { size: operand(1) as usize / 8 });
+                }
+                OP_TYPE_VECTOR => {
+                    types.insert(operand(0), Type::Vector { component: operand(1), count: operand(2) });
+                }
+                OP_TYPE_MATRIX => {
+                    types.insert(operand(0), Type::Matrix { column: operand(1), count: operand(2) });
+                }
+                OP_TYPE_IMAGE => {
+                    types.insert(operand(0), Type::Image { sampled: operand(6), format: operand(7) });
+                }
+                OP_TYPE_SAMPLER => {
+                    types.insert(operand(0), Type::Sampler);
+                }
+                OP_TYPE_SAMPLED_IMAGE => {
+                    types.insert(operand(0), Type::SampledImage);
+                }
+                OP_TYPE_ARRAY => {
+                    types.insert(operand(0), Type::Array { element: operand(1), length: operand(2) });
+                }
+                OP_TYPE_RUNTIME_ARRAY => {
+                    types.insert(operand(0), Type::RuntimeArray);
+                }
+                OP_TYPE_STRUCT => {
+                    types.insert(operand(0), Type::Struct { members: operands[1.min(operands.len())..].to_vec() });
+                }
+                OP_TYPE_POINTER => {
+                    types.insert(operand(0), Type::Pointer { pointee: operand(2) });
+                }
+                OP_CONSTANT => {
+                    constants.insert(operand(1), operand(2));
+                }
+                OP_VARIABLE => {
+                    variables.push((operand(1), operand(0), operand(2)));
+                }
+                OP_DECORATE => {
+                    decorations.insert((operand(0), operand(1)), operand(2));
+                }
+                OP_MEMBER_DECORATE if operand(2) == DECORATION_OFFSET => {
+                    member_offsets.insert((operand(0), operand(1)), operand(3));
+                }
+                _ => {}
+            }
+            i += word_count;
+        }
+
+        let module = Module { types, constants, decorations, member_offsets };
+        let mut bindings = Vec::new();
+        for (id, pointer_type, storage_class) in variables {
+            let (Some(&set), Some(&binding)) = (
+                module.decorations.get(&(id, DECORATION_DESCRIPTOR_SET)),
+                module.decorations.get(&(id, DECORATION_BINDING)),
+            ) else {
+                continue;
+            };
+            let Some(Type::Pointer { pointee }) = module.types.get(&pointer_type) else {
+                continue;
+            };
+            let Some(kind) = module.binding_kind(*pointee, storage_class) else {
+                continue;
+            };
+            bindings.push(ShaderBinding { set, binding, name: names.get(&id).cloned().unwrap_or_default(), kind });
+        }
+        bindings.sort_by_key(|binding| (binding.set, binding.binding));
+        Ok(Self { bindings })
+    }
+
+    pub fn binding(&self, set: u32, binding: u32) -> Option<&ShaderBinding> {
+        self.bindings.iter().find(|b| b.set == set && b.binding == binding)
+    }
+
+    pub fn bindings_in_set(&self, set: u32) -> impl Iterator<Item = &ShaderBinding> {
+        self.bindings.iter().filter(move |b| b.set == set)
+    }
+}
+
+struct Module {
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<(u32, u32), u32>,
+    member_offsets: HashMap<(u32, u32), u32>,
+}
+
+impl Module {
+    fn binding_kind(&self, type_id: u32, storage_class: u32) -> Option<BindingKind> {
+        // Arrays of descriptors bind like their elements
+        let mut type_id = type_id;
+        while let Some(Type::Array { element, .. }) = self.types.get(&type_id) {
+            type_id = *element;
+        }
+        match (self.types.get(&type_id)?, storage_class) {
+            (Type::Image { sampled: 2, format }, STORAGE_CLASS_UNIFORM_CONSTANT) => Some(BindingKind::StorageImage { format: *format }),
+            (Type::Image { .. } | Type::Sampler | Type::SampledImage, STORAGE_CLASS_UNIFORM_CONSTANT) => Some(BindingKind::Texture),
+            (Type::Struct { .. }, STORAGE_CLASS_STORAGE_BUFFER) => Some(BindingKind::StorageBuffer { min_size: self.size(type_id) }),
+            (Type::Struct { .. }, STORAGE_CLASS_UNIFORM) if self.decorations.contains_key(&(type_id, DECORATION_BUFFER_BLOCK)) => {
+                Some(BindingKind::StorageBuffer { min_size: self.size(type_id) })
+            }
+            (Type::Struct { .. }, STORAGE_CLASS_UNIFORM) if self.decorations.contains_key(&(type_id, DECORATION_BLOCK)) => {
+                Some(BindingKind::UniformBuffer { min_size: self.size(type_id) })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the smallest number of bytes a value of the type occupies. Runtime arrays count as
+    /// empty, so a buffer block ending in one needs only the bytes before it.
+    fn size(&self, type_id: u32) -> usize {
+        match self.types.get(&type_id) {
+            Some(Type::Scalar { size }) => *size,
+            Some(Type::Vector { component, count }) => self.size(*component) * *count as usize,
+            Some(Type::Matrix { column, count }) => {
+                let stride = self.decorations.get(&(type_id, DECORATION_MATRIX_STRIDE)).map_or(self.size(*column), |&stride| stride as usize);
+                stride * *count as usize
+            }
+            Some(Type::Array { element, length }) => {
+                let length = self.constants.get(length).copied().unwrap_or(0) as usize;
+                let stride = self.decorations.get(&(type_id, DECORATION_ARRAY_STRIDE)).map_or(self.size(*element), |&stride| stride as usize);
+                stride * length
+            }
+            Some(Type::Struct { members }) => members.iter()
+                .enumerate()
+                .map(|(i, &member)| self.member_offsets.get(&(type_id, i as u32)).copied().unwrap_or(0) as usize + self.size(member))
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+/// Decodes a nul-terminated SPIR-V literal string.
+fn literal_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words.iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Returns the texture format matching a SPIR-V storage image format, or `None` for formats no
+/// texture in this crate uses.
+pub(crate) fn data_format(image_format: u32) -> Option<DataFormat> {
+    match image_format {
+        1 => Some(DataFormat::R32G32B32A32_SFLOAT),
+        2 => Some(DataFormat::R16G16B16A16_SFLOAT),
+        3 => Some(DataFormat::R32_SFLOAT),
+        4 => Some(DataFormat::R8G8B8A8_UNORM),
+        6 => Some(DataFormat::R32G32_SFLOAT),
+        7 => Some(DataFormat::R16G16_SFLOAT),
+        9 => Some(DataFormat::R16_SFLOAT),
+        21 => Some(DataFormat::R32G32B32A32_SINT),
+        24 => Some(DataFormat::R32_SINT),
+        30 => Some(DataFormat::R32G32B32A32_UINT),
+        33 => Some(DataFormat::R32_UINT),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    /// `layout(rgba16f, set = 0, binding = 0) uniform image2DArray spectrum;` and
+    /// `layout(std430, set = 1, binding = 0) buffer B { vec4 header; vec2 data[]; };`
+    fn module() -> Vec<u32> {
+        let mut words = vec![SPIRV_MAGIC, 0x0001_0300, 0, 100, 0];
+        for op in [
+            instruction(OP_DECORATE, &[10, DECORATION_DESCRIPTOR_SET, 0]),
+            instruction(OP_DECORATE, &[10, DECORATION_BINDING, 0]),
+            instruction(OP_DECORATE, &[20, DECORATION_DESCRIPTOR_SET, 1]),
+            instruction(OP_DECORATE, &[20, DECORATION_BINDING, 0]),
+            instruction(OP_DECORATE, &[7, DECORATION_BLOCK]),
+            instruction(OP_MEMBER_DECORATE, &[7, 0, DECORATION_OFFSET, 0]),
+            instruction(OP_MEMBER_DECORATE, &[7, 1, DECORATION_OFFSET, 16]),
+            instruction(OP_TYPE_FLOAT, &[1, 32]),
+            instruction(OP_TYPE_IMAGE, &[2, 1, 1, 0, 1, 0, 2, 2]),
+            instruction(OP_TYPE_POINTER, &[3, STORAGE_CLASS_UNIFORM_CONSTANT, 2]),
+            instruction(OP_VARIABLE, &[3, 10, STORAGE_CLASS_UNIFORM_CONSTANT]),
+            instruction(OP_TYPE_VECTOR, &[4, 1, 4]),
+            instruction(OP_TYPE_VECTOR, &[5, 1, 2]),
+            instruction(OP_TYPE_RUNTIME_ARRAY, &[6, 5]),
+            instruction(OP_TYPE_STRUCT, &[7, 4, 6]),
+            instruction(OP_TYPE_POINTER, &[8, STORAGE_CLASS_STORAGE_BUFFER, 7]),
+            instruction(OP_VARIABLE, &[8, 20, STORAGE_CLASS_STORAGE_BUFFER]),
+        ] {
+            words.extend(op);
+        }
+        words
+    }
+
+    #[test]
+    fn reflects_storage_images_and_buffers() {
+        let reflection = ShaderReflection::from_words(&module()).unwrap();
+        assert_eq!(reflection.binding(0, 0).unwrap().kind, BindingKind::StorageImage { format: 2 });
+        assert_eq!(reflection.binding(1, 0).unwrap().kind, BindingKind::StorageBuffer { min_size: 16 });
+        assert_eq!(data_format(2), Some(DataFormat::R16G16B16A16_SFLOAT));
+        assert!(reflection.binding(0, 1).is_none());
+    }
+
+    #[test]
+    fn rejects_truncated_bytecode() {
+        let mut words = module();
+        words.truncate(words.len() - 1);
+        assert_eq!(ShaderReflection::from_words(&words).unwrap_err(), ReflectionError::Truncated);
+        assert_eq!(ShaderReflection::from_words(&[0; 5]).unwrap_err(), ReflectionError::NotSpirv);
+    }
+}
@@ -0,0 +1,51 @@
+/// A compute shader compiled into the library, so the addon works wherever it is installed.
+pub(crate) struct EmbeddedShader {
+    /// File name without extension, also the key of `Ocean::shader_overrides`.
+    pub name: &'static str,
+    source: &'static str,
+}
+
+macro_rules! embedded_shader {
+    ($name:literal) => {
+        EmbeddedShader { name: $name, source: include_str!(concat!("../shaders/compute/", $name, ".glsl")) }
+    };
+}
+
+pub(crate) const SPECTRUM_COMPUTE: EmbeddedShader = embedded_shader!("spectrum_compute");
+pub(crate) const SPECTRUM_MODULATE: EmbeddedShader = embedded_shader!("spectrum_modulate");
+pub(crate) const FFT_BUTTERFLY: EmbeddedShader = embedded_shader!("fft_butterfly");
+pub(crate) const FFT_COMPUTE: EmbeddedShader = embedded_shader!("fft_compute");
+pub(crate) const FFT_STAGE: EmbeddedShader = embedded_shader!("fft_stage");
+pub(crate) const TRANSPOSE: EmbeddedShader = embedded_shader!("transpose");
+pub(crate) const FFT_UNPACK: EmbeddedShader = embedded_shader!("fft_unpack");
+
+/// Every embedded shader, for checking override names.
+pub(crate) const ALL: [&EmbeddedShader; 7] = [&SPECTRUM_COMPUTE, &SPECTRUM_MODULATE, &FFT_BUTTERFLY, &FFT_COMPUTE, &FFT_STAGE, &TRANSPOSE, &FFT_UNPACK];
+
+impl EmbeddedShader {
+    /// GLSL source for `RDShaderSource`, without the `#[compute]` stage marker of Godot's
+    /// `.glsl` shader files.
+    pub fn glsl(&self) -> &'static str {
+        strip_stage_marker(self.source)
+    }
+}
+
+fn strip_stage_marker(source: &str) -> &str {
+    let trimmed = source.trim_start();
+    match trimmed.strip_prefix("#[compute]") {
+        Some(rest) => rest,
+        None => source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_shaders_start_with_their_version() {
+        for shader in ALL {
+            assert!(shader.glsl().trim_start().starts_with("#version"), "{} has no #version after its stage marker", shader.name);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+#[cfg(feature = "simulation-thread")]
+pub(crate) use worker::SimulationThread;
+
+/// Without the `simulation-thread` feature there is no worker, and no value of this type.
+#[cfg(not(feature = "simulation-thread"))]
+pub(crate) enum SimulationThread {}
+
+#[cfg(feature = "simulation-thread")]
+mod worker {
+    use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+    use std::thread::JoinHandle;
+    use godot::prelude::*;
+    use godot::classes::rendering_device::{DataFormat, TextureUsageBits};
+    use godot::classes::{RdTextureView, RenderingDevice, RenderingServer};
+    use crate::compute_pipeline::RecordedDispatch;
+    use crate::rendering_context::RenderingContext;
+
+    /// One step of the compute list of a `SimulationJob`.
+    enum Pass {
+        Dispatch(RecordedDispatch),
+        Barrier,
+    }
+
+    /// Device work of one ocean update, recorded as plain data on the main thread.
+    #[derive(Default)]
+    struct SimulationJob {
+        /// Buffer writes made before the compute list, as (buffer, offset, data).
+        buffer_updates: Vec<(Rid, u32, Vec<u8>)>,
+        passes: Vec<Pass>,
+    }
+
+    impl SimulationJob {
+        fn record(&self, device: &mut Gd<RenderingDevice>) {
+            for (buffer, offset, data) in &self.buffer_updates {
+                device.buffer_update(*buffer, *offset, data.len() as u32, &PackedByteArray::from(data.as_slice()));
+            }
+            if self.passes.is_empty() {
+                return;
+            }
+            let compute_list = device.compute_list_begin();
+            for pass in &self.passes {
+                match pass {
+                    Pass::Dispatch(dispatch) => dispatch.record(device, compute_list),
+                    Pass::Barrier => device.compute_list_add_barrier(compute_list),
+                }
+            }
+            device.compute_list_end();
+        }
+    }
+
+    /// A job together with the maps to copy back once the device is done with it.
+    struct WorkItem {
+        job: SimulationJob,
+        maps: [Rid; 2],
+        num_layers: u32,
+    }
+
+    /// Texels of every layer of the displacement and normal maps, as read back from the local device.
+    struct CopiedMaps {
+        layers: [Vec<Vec<u8>>; 2],
+    }
+
+    /// Worker thread that owns a local rendering device and runs the ocean simulation on it, so
+    /// heavy updates stay out of the main render graph. The main thread creates the device's
+    /// resources and records each update into a job; the worker records the job into a compute
+    /// list, submits it, waits for it and reads the maps back. The copies are then uploaded to
+    /// shared maps on the main device, which lag one update behind. At most one job is in flight.
+    ///
+    /// The device is created on the worker, since Godot only lets its creating thread record,
+    /// submit, sync and read back. Only engine classes are used on the worker, which gdext allows
+    /// with `experimental-threads`.
+    pub(crate) struct SimulationThread {
+        jobs: Option<Sender<WorkItem>>,
+        results: Receiver<CopiedMaps>,
+        worker: Option<JoinHandle<()>>,
+        in_flight: bool,
+        /// Work recorded since the last submission.
+        job: SimulationJob,
+        /// The displacement and normal maps on the local device, and how many of their layers to copy.
+        maps: [Rid; 2],
+        num_layers: u32,
+        /// Context of the main device, holding `shared_maps`.
+        shared_context: Gd<RenderingContext>,
+        /// Main device copies of `maps`, which the materials sample.
+        shared_maps: [Rid; 2],
+        /// The most recent copy of the maps, for readback without touching either device.
+        copied_maps: Option<CopiedMaps>,
+    }
+
+    impl SimulationThread {
+        /// Starts the worker and returns it with its local device, or `None` if no local device
+        /// could be created, e.g. with the Compatibility renderer.
+        pub fn new() -> Option<(Self, Gd<RenderingDevice>)> {
+            let (device_sender, device_receiver) = channel();
+            let (jobs, job_receiver) = channel::<WorkItem>();
+            let (result_sender, results) = channel();
+            let worker = std::thread::spawn(move || {
+                let Some(mut device) = RenderingServer::singleton().create_local_rendering_device() else {
+                    let _ = device_sender.send(None);
+                    return;
+                };
+                let _ = device_sender.send(Some(device.instance_id()));
+                for item in job_receiver {
+                    item.job.record(&mut device);
+                    device.submit();
+                    device.sync();
+                    let layers = item.maps.map(|texture| {
+                        (0..item.num_layers).map(|layer| device.texture_get_data(texture, layer).to_vec()).collect()
+                    });
+                    if result_sender.send(CopiedMaps { layers }).is_err() {
+                        return;
+                    }
+                }
+            });
+            let Ok(Some(device)) = device_receiver.recv() else {
+                let _ = worker.join();
+                return None;
+            };
+            let mut shared_context = RenderingContext::new_gd();
+            shared_context.bind_mut().initialize(RenderingServer::singleton().get_rendering_device());
+            let thread = Self {
+                jobs: Some(jobs),
+                results,
+                worker: Some(worker),
+                in_flight: false,
+                job: SimulationJob::default(),
+                maps: [Rid::Invalid; 2],
+                num_layers: 0,
+                shared_context,
+                shared_maps: [Rid::Invalid; 2],
+                copied_maps: None,
+            };
+            Some((thread, Gd::from_instance_id(device)))
+        }
+
+        /// Copies `maps`, the displacement and normal maps of the local device, to new maps on the
+        /// main device after every job.
+        pub fn share_maps(&mut self, maps: [Rid; 2], map_size: i32, num_layers: u32) {
+            self.maps = maps;
+            self.num_layers = num_layers;
+            let mut context = self.shared_context.bind_mut();
+            self.shared_maps = [(); 2].map(|_| context.create_texture(
+                Vector2i { x: map_size, y: map_size },
+                DataFormat::R16G16B16A16_SFLOAT,
+                TextureUsageBits::SAMPLING_BIT | TextureUsageBits::CAN_UPDATE_BIT | TextureUsageBits::CAN_COPY_FROM_BIT,
+                num_layers,
+                RdTextureView::new_gd(),
+                Array::new()
+            ).rid);
+        }
+
+        /// Main device copy of `map`, or an invalid RID if it isn't shared.
+        pub fn shared_map(&self, map: Rid) -> Rid {
+            self.map_index(map).map_or(Rid::Invalid, |index| self.shared_maps[index])
+        }
+
+        fn map_index(&self, map: Rid) -> Option<usize> {
+            self.maps.iter().position(|&local| map.is_valid() && local == map)
+        }
+
+        pub fn buffer_update(&mut self, buffer: Rid, offset: u32, data: &PackedByteArray) {
+            self.job.buffer_updates.push((buffer, offset, data.to_vec()));
+        }
+
+        pub fn dispatch(&mut self, dispatch: RecordedDispatch) {
+            self.job.passes.push(Pass::Dispatch(dispatch));
+        }
+
+        pub fn barrier(&mut self) {
+            self.job.passes.push(Pass::Barrier);
+        }
+
+        /// Hands the recorded work to the worker, after collecting the job in flight.
+        pub fn submit(&mut self) {
+            self.collect(true);
+            let item = WorkItem { job: std::mem::take(&mut self.job), maps: self.maps, num_layers: self.num_layers };
+            self.in_flight = self.jobs.as_ref().is_some_and(|jobs| jobs.send(item).is_ok());
+            if !self.in_flight {
+                godot_error!("simulation_thread.rs: The simulation thread has stopped");
+            }
+        }
+
+        /// Uploads the maps the worker copied to the shared maps, waiting for it if `wait` is set.
+        /// Returns false while the worker is still busy.
+        pub fn collect(&mut self, wait: bool) -> bool {
+            if !self.in_flight {
+                return true;
+            }
+            let maps = match if wait { self.results.recv().map_err(|_| TryRecvError::Disconnected) } else { self.results.try_recv() } {
+                Ok(maps) => maps,
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => {
+                    godot_error!("simulation_thread.rs: The simulation thread stopped before finishing its job");
+                    self.in_flight = false;
+                    return true;
+                }
+            };
+            self.in_flight = false;
+            let mut context = self.shared_context.bind_mut();
+            for (&texture, layers) in self.shared_maps.iter().zip(&maps.layers) {
+                for (layer, data) in layers.iter().enumerate() {
+                    context.texture_update(texture, layer as u32, &PackedByteArray::from(data.as_slice()));
+                }
+            }
+            self.copied_maps = Some(maps);
+            true
+        }
+
+        /// The first `num_layers` layers of the last copy of `map`, after waiting for the job in
+        /// flight. Empty if `map` isn't shared.
+        pub fn copied_layers(&mut self, map: Rid, num_layers: u32) -> Vec<PackedByteArray> {
+            self.collect(true);
+            let Some(index) = self.map_index(map) else {
+                return Vec::new();
+            };
+            self.copied_maps.as_ref().map_or(Vec::new(), |maps| {
+                maps.layers[index].iter()
+                    .take(num_layers as usize)
+                    .map(|data| PackedByteArray::from(data.as_slice()))
+                    .collect()
+            })
+        }
+    }
+
+    impl Drop for SimulationThread {
+        fn drop(&mut self) {
+            // Closing the channel ends the worker once its current job is done
+            self.jobs = None;
+            if let Some(worker) = self.worker.take() {
+                let _ = worker.join();
+            }
+        }
+    }
+}
@@ -0,0 +1,646 @@
+use std::f32::consts::PI;
+use godot::prelude::*;
+use godot::classes::Curve;
+use crate::push_constant::PushConstant;
+use crate::wave_cascade_parameters::{DirectionalSpreading, SpectrumModel, WaveCascadeParameters};
+use crate::wave_generator::{jonswap_alpha, jonswap_peak_angular_frequency};
+
+/// Number of samples per cascade in the custom spreading table, matching `SPREADING_TABLE_SIZE`
+/// in `spectrum_compute.glsl`.
+pub(crate) const SPREADING_TABLE_SIZE: usize = 32;
+
+/// Peak shape of JONSWAP and TMA cascades unless changed, from the JONSWAP study.
+pub(crate) const DEFAULT_JONSWAP_GAMMA: f32 = 3.3;
+pub(crate) const DEFAULT_JONSWAP_SIGMA: (f32, f32) = (0.07, 0.09);
+pub(crate) const DEFAULT_JONSWAP_BETA: f32 = 1.25;
+/// Number of trapezoids used to integrate spectral moments.
+const SPECTRAL_MOMENT_STEPS: usize = 2048;
+/// Number of frequencies and directions the directional spectrum is sampled at for `SpectralStatistics`.
+const STATISTICS_FREQUENCY_STEPS: usize = 512;
+const STATISTICS_DIRECTION_STEPS: usize = 128;
+
+/// A smaller cascade takes over from the next larger one at wavelengths this many times shorter
+/// than its own tile, so neither cascade has to represent waves close to its resolution limit.
+const CASCADE_BAND_WAVES_PER_TILE: f32 = 6.0;
+
+/// The spectrum push constants of `spectrum_compute.glsl`, derived from a cascade's parameters.
+/// Both the GPU pipeline and `ReferenceCascade` build their initial spectrum from this.
+///
+/// Sources: Jerry Tessendorf - Simulating Ocean Water
+///          Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+///          Yoshimi Goda - Random Seas and Design of Maritime Structures
+pub(crate) struct SpectrumSettings {
+    seed: Vector2i,
+    tile_length: Vector2,
+    model: SpectrumModel,
+    alpha: f32,
+    peak_frequency: f32,
+    wind_speed: f32,
+    angle: f32,
+    swell: f32,
+    detail: f32,
+    spread: f32,
+    depth: f32,
+    gravity: f32,
+    /// Peak enhancement factor
+    gamma: f32,
+    /// Peak shape, only used by JONSWAP and TMA
+    beta: f32,
+    /// Peak width below and above the peak frequency
+    sigma: Vector2,
+    spreading: DirectionalSpreading,
+    /// Only used by `DirectionalSpreading::Custom`. The GPU reads it from a storage buffer.
+    spreading_table: [f32; SPREADING_TABLE_SIZE],
+    /// Significant wave height of the swell, 0 disables it
+    swell_height: f32,
+    swell_peak_frequency: f32,
+    swell_angle: f32,
+    /// cos-2s shaping parameter of the swell
+    swell_spreading: f32,
+    /// Band of k this cascade covers
+    wavenumber_cutoff: Vector2,
+}
+
+impl SpectrumSettings {
+    pub fn new(params: &WaveCascadeParameters) -> Self {
+        let wind_speed = params.wind_speed;
+        let fetch_length = params.fetch_length * 1e3;
+        let gravity = params.gravity;
+        let jonswap_sigma = Vector2::new(params.jonswap_sigma_below, params.jonswap_sigma_above);
+        let (alpha, peak_frequency, gamma, sigma) = match params.spectrum_model {
+            SpectrumModel::Phillips => (params.phillips_amplitude, phillips_peak_angular_frequency(wind_speed, gravity), 1.0, jonswap_sigma),
+            SpectrumModel::PiersonMoskowitz => (8.1e-3, pierson_moskowitz_peak_angular_frequency(wind_speed, gravity), 1.0, jonswap_sigma),
+            SpectrumModel::Jonswap | SpectrumModel::Tma => (
+                jonswap_alpha(wind_speed, fetch_length, gravity),
+                jonswap_peak_angular_frequency(wind_speed, fetch_length, gravity),
+                params.jonswap_gamma,
+                jonswap_sigma,
+            ),
+            SpectrumModel::DonelanBanner => {
+                let peak_frequency = jonswap_peak_angular_frequency(wind_speed, fetch_length, gravity);
+                let (alpha, gamma, sigma) = donelan_banner_shape(wind_speed, peak_frequency, gravity);
+                (alpha, peak_frequency, gamma, Vector2::splat(sigma))
+            }
+        };
+        Self {
+            seed: params.spectrum_seed,
+            tile_length: params.tile_length,
+            model: params.spectrum_model,
+            alpha,
+            peak_frequency,
+            wind_speed,
+            angle: params.wind_direction.to_radians(),
+            swell: params.swell,
+            detail: params.detail,
+            spread: params.spread,
+            depth: params.depth,
+            gravity,
+            gamma,
+            beta: params.jonswap_beta,
+            sigma,
+            spreading: params.directional_spreading,
+            spreading_table: spreading_table(params.custom_spreading.as_ref()),
+            swell_height: params.swell_height,
+            swell_peak_frequency: 2.0 * PI / params.swell_period,
+            swell_angle: params.swell_direction.to_radians(),
+            swell_spreading: swell_shaping(params.swell_spread.to_radians()),
+            wavenumber_cutoff: params.wavenumber_cutoff,
+        }
+    }
+
+    /// Settings of a JONSWAP wind sea with the default peak shape and no swell, for sizing sea
+    /// states without a `WaveCascadeParameters`. `fetch_length` is in km.
+    pub fn jonswap(wind_speed: f32, fetch_length: f32, gravity: f32) -> Self {
+        let fetch_length = fetch_length * 1e3;
+        Self {
+            seed: Vector2i::ZERO,
+            tile_length: Vector2::splat(50.0),
+            model: SpectrumModel::Jonswap,
+            alpha: jonswap_alpha(wind_speed, fetch_length, gravity),
+            peak_frequency: jonswap_peak_angular_frequency(wind_speed, fetch_length, gravity),
+            wind_speed,
+            angle: 0.0,
+            swell: 0.0,
+            detail: 1.0,
+            spread: 0.0,
+            depth: f32::MAX,
+            gravity,
+            gamma: DEFAULT_JONSWAP_GAMMA,
+            beta: DEFAULT_JONSWAP_BETA,
+            sigma: Vector2::new(DEFAULT_JONSWAP_SIGMA.0, DEFAULT_JONSWAP_SIGMA.1),
+            spreading: DirectionalSpreading::default(),
+            spreading_table: [0.0; SPREADING_TABLE_SIZE],
+            swell_height: 0.0,
+            swell_peak_frequency: 1.0,
+            swell_angle: 0.0,
+            swell_spreading: 0.0,
+            wavenumber_cutoff: Vector2::new(0.0, f32::MAX),
+        }
+    }
+
+    /// Packs the settings in the order of the `PushConstants` block in `spectrum_compute.glsl`.
+    pub fn push_constant(&self, cascade_index: u32, spectrum_layer: u32) -> PushConstant {
+        PushConstant::new()
+            .push_ivec2(self.seed)
+            .push_vec2(self.tile_length)
+            .push_f32(self.alpha)
+            .push_f32(self.peak_frequency)
+            .push_f32(self.wind_speed)
+            .push_f32(self.angle)
+            .push_f32(self.depth)
+            .push_f32(self.swell)
+            .push_f32(self.detail)
+            .push_f32(self.spread)
+            .push_u32(cascade_index)
+            .push_u32(self.model as u32)
+            .push_f32(self.gamma)
+            .push_f32(self.beta)
+            .push_u32(self.spreading as u32)
+            .push_f32(self.gravity)
+            .push_vec2(self.sigma)
+            .push_f32(self.swell_height)
+            .push_f32(self.swell_peak_frequency)
+            .push_f32(self.swell_angle)
+            .push_f32(self.swell_spreading)
+            .push_vec2(self.wavenumber_cutoff)
+            .push_u32(spectrum_layer)
+    }
+
+    /// Port of `get_spectrum_amplitude` in `spectrum_compute.glsl`.
+    pub fn amplitude(&self, id: Vector2i, map_size: i32) -> Vector2 {
+        let dk = Vector2::splat(2.0 * PI) / self.tile_length;
+        let k_vec = (Vector2::new(id.x as f32, id.y as f32) - Vector2::splat(map_size as f32 * 0.5)) * dk; // Wave direction
+        let k = k_vec.length() + 1e-6;
+        let theta = k_vec.x.atan2(k_vec.y);
+        if k < self.wavenumber_cutoff.x || k >= self.wavenumber_cutoff.y {
+            return Vector2::ZERO;
+        }
+
+        let (w, d_w) = dispersion_relation(k, self.depth, self.gravity);
+        let w_norm = d_w / k * dk.x * dk.y;
+        let detail_falloff = (-(1.0 - self.detail) * (1.0 - self.detail) * k * k).exp();
+        let seed = id + self.seed;
+        gaussian(hash(seed.x as u32, seed.y as u32)) * (2.0 * self.directional_spectrum(w, theta) * detail_falloff * w_norm).sqrt()
+    }
+
+    /// Returns the directional spectrum E(w, theta) of the wind sea and swell combined.
+    fn directional_spectrum(&self, w: f32, theta: f32) -> f32 {
+        let s = self.omnidirectional_spectrum(w);
+        let d = 0.5 / PI + (self.directional_spread(w, theta) - 0.5 / PI) * (1.0 - self.spread);
+        let swell_s = self.swell_spectrum(w);
+        let swell_d = longuet_higgins_function(self.swell_spreading, theta - self.swell_angle);
+        s * d + swell_s * swell_d
+    }
+
+    /// Returns whether waves of angular frequency `w` fall in this cascade's band.
+    fn in_band(&self, w: f32) -> bool {
+        let low = dispersion_relation(self.wavenumber_cutoff.x, self.depth, self.gravity).0;
+        let high = dispersion_relation(self.wavenumber_cutoff.y, self.depth, self.gravity).0;
+        w >= low && w < high
+    }
+
+    /// Returns the `order`-th moment of the frequency spectrum, wind sea and swell combined. The
+    /// cascade's band cutoffs, `spread` and `detail` are ignored, so this describes the whole sea
+    /// state rather than what the cascade renders.
+    pub fn spectral_moment(&self, order: i32) -> f32 {
+        // Integrate in log space, which samples the steep low frequency flank of the peak densely
+        let w_min = 0.05 * self.peak_frequency.min(self.swell_peak_frequency);
+        let w_max = 100.0 * self.peak_frequency.max(self.swell_peak_frequency);
+        let (ln_min, ln_max) = (w_min.ln(), w_max.ln());
+        let d_ln_w = (ln_max - ln_min) / SPECTRAL_MOMENT_STEPS as f32;
+        let integrand = |ln_w: f32| {
+            let w = ln_w.exp();
+            w.powi(order + 1) * (self.omnidirectional_spectrum(w) + self.swell_spectrum(w))
+        };
+        let inner: f32 = (1..SPECTRAL_MOMENT_STEPS).map(|i| integrand(ln_min + i as f32 * d_ln_w)).sum();
+        d_ln_w * (inner + 0.5 * (integrand(ln_min) + integrand(ln_max)))
+    }
+
+    /// Returns the significant wave height H_s = 4 sqrt(m0).
+    pub fn significant_wave_height(&self) -> f32 {
+        4.0 * self.spectral_moment(0).sqrt()
+    }
+
+    /// Port of `omnidirectional_spectrum` in `spectrum_compute.glsl`.
+    fn omnidirectional_spectrum(&self, w: f32) -> f32 {
+        let (w_p, g) = (self.peak_frequency, self.gravity);
+        match self.model {
+            SpectrumModel::Phillips => phillips_spectrum(w, self.wind_speed, self.alpha, g),
+            SpectrumModel::PiersonMoskowitz => pierson_moskowitz_spectrum(w, w_p, self.alpha, g),
+            SpectrumModel::Jonswap => jonswap_spectrum(w, w_p, self.alpha, self.gamma, self.beta, self.sigma, g),
+            SpectrumModel::Tma => jonswap_spectrum(w, w_p, self.alpha, self.gamma, self.beta, self.sigma, g) * kitaigorodskii_depth_attenuation(w, self.depth, g),
+            SpectrumModel::DonelanBanner => donelan_banner_spectrum(w, w_p, self.alpha, self.gamma, self.sigma, g),
+        }
+    }
+
+    /// Port of `swell_spectrum` in `spectrum_compute.glsl`.
+    // Source: Yoshimi Goda - Random Seas and Design of Maritime Structures (JONSWAP in terms of H_s and T_p)
+    fn swell_spectrum(&self, w: f32) -> f32 {
+        if self.swell_height <= 0.0 {
+            return 0.0;
+        }
+        let swell_gamma: f32 = 3.3;
+        let w_p = self.swell_peak_frequency;
+        let r = peak_enhancement_exponent(w, w_p, Vector2::new(0.07, 0.09));
+        let bretschneider = 0.3125 * self.swell_height * self.swell_height * w_p.powi(4) / w.powi(5) * (-1.25 * (w_p / w).powi(4)).exp();
+        // The swell feels the bottom only when the wind sea does
+        let depth_attenuation = match self.model {
+            SpectrumModel::Tma => kitaigorodskii_depth_attenuation(w, self.depth, self.gravity),
+            _ => 1.0,
+        };
+        bretschneider * (1.0 - 0.287 * swell_gamma.ln()) * swell_gamma.powf(r) * depth_attenuation
+    }
+
+    /// Port of `directional_spread` in `spectrum_compute.glsl`.
+    fn directional_spread(&self, w: f32, theta: f32) -> f32 {
+        let w_p = self.peak_frequency;
+        let theta = (theta - self.angle + PI).rem_euclid(2.0 * PI) - PI; // Wrap to [-PI, PI)
+        let s_xi = 16.0 * (w_p / w).tanh() * self.swell * self.swell; // Shaping parameter w/ swell
+        match self.spreading {
+            DirectionalSpreading::Hasselmann => longuet_higgins_function(hasselmann_shaping(w, w_p, self.wind_speed, self.gravity) + s_xi, theta),
+            DirectionalSpreading::Mitsuyasu => longuet_higgins_function(mitsuyasu_shaping(w, w_p, self.wind_speed, self.gravity) + s_xi, theta),
+            DirectionalSpreading::DonelanBanner => donelan_banner_directional_spread(w, w_p, theta),
+            DirectionalSpreading::PositiveCosineSquared => positive_cosine_squared_directional_spread(theta),
+            DirectionalSpreading::Custom => self.custom_directional_spread(theta),
+        }
+    }
+
+    /// Linearly interpolates the custom spreading table, which covers |theta| from 0 to PI.
+    fn custom_directional_spread(&self, theta: f32) -> f32 {
+        let x = theta.abs() / PI * (SPREADING_TABLE_SIZE - 1) as f32;
+        let i = (x as usize).min(SPREADING_TABLE_SIZE - 2);
+        let t = x - i as f32;
+        self.spreading_table[i] * (1.0 - t) + self.spreading_table[i + 1] * t
+    }
+}
+
+/// Bulk statistics of a sea state, integrated from its directional spectrum.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SpectralStatistics {
+    /// H_s = 4 sqrt(m0), in m
+    pub significant_wave_height: f32,
+    /// Period of the most energetic waves, in s
+    pub peak_period: f32,
+    /// T_z = 2 PI sqrt(m0 / m2), in s
+    pub zero_crossing_period: f32,
+    /// Energy-weighted mean direction the waves travel in, in degrees like `wind_direction`
+    pub mean_direction: f32,
+    /// Significant steepness 2 PI H_s / (g T_p²)
+    pub steepness: f32,
+}
+
+impl SpectralStatistics {
+    /// Integrates the spectra of `cascades`, summed. With `band_limited` each cascade only
+    /// contributes the wavenumbers it renders; otherwise each contributes its whole spectrum, which
+    /// only makes sense for a single cascade. Depth attenuation and directional spreading are
+    /// included, `detail` is not.
+    pub fn new(cascades: &[SpectrumSettings], band_limited: bool) -> Self {
+        let Some(first) = cascades.first() else {
+            return Self { significant_wave_height: 0.0, peak_period: 0.0, zero_crossing_period: 0.0, mean_direction: 0.0, steepness: 0.0 };
+        };
+        let w_min = cascades.iter().map(|c| 0.05 * c.peak_frequency.min(c.swell_peak_frequency)).fold(f32::MAX, f32::min);
+        let w_max = cascades.iter().map(|c| 100.0 * c.peak_frequency.max(c.swell_peak_frequency)).fold(0.0, f32::max);
+        let (ln_min, ln_max) = (w_min.ln(), w_max.ln());
+        let d_ln_w = (ln_max - ln_min) / STATISTICS_FREQUENCY_STEPS as f32;
+        let d_theta = 2.0 * PI / STATISTICS_DIRECTION_STEPS as f32;
+
+        let (mut m0, mut m2, mut direction) = (0.0, 0.0, Vector2::ZERO);
+        let (mut peak_frequency, mut peak_energy) = (first.peak_frequency, 0.0);
+        for i in 0..=STATISTICS_FREQUENCY_STEPS {
+            let w = (ln_min + i as f32 * d_ln_w).exp();
+            let mut energy = 0.0;
+            let mut energy_direction = Vector2::ZERO;
+            for cascade in cascades.iter().filter(|c| !band_limited || c.in_band(w)) {
+                for j in 0..STATISTICS_DIRECTION_STEPS {
+                    let theta = -PI + j as f32 * d_theta;
+                    let e = cascade.directional_spectrum(w, theta) * d_theta;
+                    energy += e;
+                    energy_direction += Vector2::new(theta.sin(), theta.cos()) * e;
+                }
+            }
+            if energy > peak_energy {
+                (peak_frequency, peak_energy) = (w, energy);
+            }
+            // Trapezoid rule in log space, where dw = w d(ln w)
+            let weight = if i == 0 || i == STATISTICS_FREQUENCY_STEPS { 0.5 * d_ln_w } else { d_ln_w } * w;
+            m0 += energy * weight;
+            m2 += energy * w * w * weight;
+            direction += energy_direction * weight;
+        }
+
+        let significant_wave_height = 4.0 * m0.sqrt();
+        let peak_period = 2.0 * PI / peak_frequency;
+        Self {
+            significant_wave_height,
+            peak_period,
+            zero_crossing_period: if m2 > 0.0 { 2.0 * PI * (m0 / m2).sqrt() } else { 0.0 },
+            mean_direction: direction.x.atan2(direction.y).to_degrees(),
+            steepness: 2.0 * PI * significant_wave_height / (first.gravity * peak_period * peak_period),
+        }
+    }
+
+    pub fn to_dictionary(self) -> Dictionary {
+        vdict! {
+            "significant_wave_height": self.significant_wave_height,
+            "peak_period": self.peak_period,
+            "zero_crossing_period": self.zero_crossing_period,
+            "mean_direction": self.mean_direction,
+            "steepness": self.steepness,
+        }
+    }
+}
+
+/// Splits the wavenumbers between cascades so each wave is only generated by one of them. Returns
+/// the `(low, high)` cutoff of every cascade, in the order of `tile_lengths` and `map_sizes`. Each
+/// boundary sits `CASCADE_BAND_WAVES_PER_TILE` waves into the smaller cascade, clamped to the
+/// Nyquist limit of the larger one at its own map size. When the clamp applies, the boundary can
+/// fall below the fundamental of the smaller cascade, and the wavenumbers between the two are not
+/// generated by either cascade.
+pub(crate) fn wavenumber_cutoffs(tile_lengths: &[Vector2], map_sizes: &[i32]) -> Vec<Vector2> {
+    let sizes: Vec<f32> = tile_lengths.iter().map(|tile_length| tile_length.x.max(tile_length.y)).collect();
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| sizes[b].total_cmp(&sizes[a]));
+
+    let mut cutoffs = vec![Vector2::new(0.0, f32::MAX); sizes.len()];
+    for pair in order.windows(2) {
+        let (larger, smaller) = (sizes[pair[0]], sizes[pair[1]]);
+        let nyquist = PI * map_sizes[pair[0]] as f32 / larger;
+        let boundary = (CASCADE_BAND_WAVES_PER_TILE * 2.0 * PI / smaller).min(nyquist);
+        cutoffs[pair[0]].y = boundary;
+        cutoffs[pair[1]].x = boundary;
+    }
+    cutoffs
+}
+
+/// Samples `curve` across its domain into a table covering |theta| from 0 to PI, normalized so that
+/// the spreading integrates to 1 over [-PI, PI] under linear interpolation. Falls back to uniform
+/// spreading when there is no curve or it integrates to 0.
+pub(crate) fn spreading_table(curve: Option<&Gd<Curve>>) -> [f32; SPREADING_TABLE_SIZE] {
+    let uniform = [0.5 / PI; SPREADING_TABLE_SIZE];
+    let Some(curve) = curve else {
+        return uniform;
+    };
+    let (min, max) = (curve.get_min_domain(), curve.get_max_domain());
+    let mut table: [f32; SPREADING_TABLE_SIZE] = std::array::from_fn(|i| {
+        let t = i as f32 / (SPREADING_TABLE_SIZE - 1) as f32;
+        curve.sample(min + (max - min) * t).max(0.0)
+    });
+    // Trapezoidal rule over [0, PI], doubled since the spreading is symmetric around the wind
+    let d_theta = PI / (SPREADING_TABLE_SIZE - 1) as f32;
+    let integral = 2.0 * d_theta * (table.iter().sum::<f32>() - 0.5 * (table[0] + table[SPREADING_TABLE_SIZE - 1]));
+    if integral <= f32::EPSILON {
+        return uniform;
+    }
+    table.iter_mut().for_each(|x| *x /= integral);
+    table
+}
+
+// Peak of the Phillips spectrum below, where its derivative w.r.t. w vanishes.
+fn phillips_peak_angular_frequency(wind_speed: f32, gravity: f32) -> f32 {
+    0.8f32.powf(0.25) * gravity / wind_speed
+}
+
+// Source: https://wikiwaves.org/Ocean-Wave_Spectra#Pierson-Moskowitz_Spectrum
+fn pierson_moskowitz_peak_angular_frequency(wind_speed: f32, gravity: f32) -> f32 {
+    0.877 * gravity / wind_speed
+}
+
+/// Returns alpha, gamma and sigma of the Donelan-Banner spectrum from the inverse wave age.
+// Source: Donelan, Hamilton & Hui - Directional Spectra of Wind-Generated Waves
+fn donelan_banner_shape(wind_speed: f32, peak_frequency: f32, gravity: f32) -> (f32, f32, f32) {
+    // The fit is only valid for inverse wave ages between 0.83 and 5
+    let inverse_wave_age = (wind_speed * peak_frequency / gravity).clamp(0.83, 5.0);
+    let alpha = 0.006 * inverse_wave_age.powf(0.55);
+    let gamma = if inverse_wave_age < 1.0 { 1.7 } else { 1.7 + 6.0 * inverse_wave_age.ln() };
+    let sigma = 0.08 * (1.0 + 4.0 / inverse_wave_age.powi(3));
+    (alpha, gamma, sigma)
+}
+
+// --- HELPER FUNCTIONS ---
+// Source: https://www.shadertoy.com/view/Xt3cDn
+fn hash(x: u32, y: u32) -> Vector2 {
+    let mut h32 = y.wrapping_add(374761393).wrapping_add(x.wrapping_mul(3266489917));
+    h32 = 2246822519u32.wrapping_mul(h32 ^ (h32 >> 15));
+    h32 = 3266489917u32.wrapping_mul(h32 ^ (h32 >> 13));
+    let n = h32 ^ (h32 >> 16);
+    let rz = [n, n.wrapping_mul(48271)];
+    Vector2::new(((rz[0] >> 1) & 0x7FFFFFFF) as f32, ((rz[1] >> 1) & 0x7FFFFFFF) as f32) / 0x7FFFFFFF as f32
+}
+
+/// Samples a 2D-bivariate normal distribution with the Box-Muller transform.
+fn gaussian(x: Vector2) -> Vector2 {
+    let r = (-2.0 * x.x.ln()).sqrt();
+    let theta = 2.0 * PI * x.y;
+    Vector2::new(r * theta.cos(), r * theta.sin())
+}
+
+// --- SPECTRUM-RELATED FUNCTIONS ---
+// Source: Jerry Tessendorf - Simulating Ocean Water
+/// Returns the dispersion relation and its derivative w.r.t. k.
+fn dispersion_relation(k: f32, depth: f32, gravity: f32) -> (f32, f32) {
+    let a = k * depth;
+    let b = a.tanh();
+    let dispersion_relation = (gravity * k * b).sqrt();
+    let d_dispersion_relation = 0.5 * gravity * (b + a * (1.0 - b * b)) / dispersion_relation;
+    (dispersion_relation, d_dispersion_relation)
+}
+
+/// Normalization factor approximation for Longuet-Higgins function.
+fn longuet_higgins_normalization(s: f32) -> f32 {
+    let a = s.sqrt();
+    if s < 0.4 {
+        (0.5 / PI) + s * (0.220636 + s * (-0.109 + s * 0.090))
+    } else {
+        (a * 0.5 + (1.0 / a) * 0.0625) / PI.sqrt()
+    }
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+fn longuet_higgins_function(s: f32, theta: f32) -> f32 {
+    longuet_higgins_normalization(s) * (theta * 0.5).cos().abs().powf(2.0 * s)
+}
+
+/// Returns the cos-2s shaping parameter whose spreading has the given directional standard deviation.
+fn swell_shaping(standard_deviation: f32) -> f32 {
+    (2.0 / (standard_deviation * standard_deviation) - 1.0).max(0.0)
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+fn hasselmann_shaping(w: f32, w_p: f32, wind_speed: f32, gravity: f32) -> f32 {
+    let p = w / w_p;
+    if w <= w_p {
+        6.97 * p.abs().powf(4.06)
+    } else {
+        9.77 * p.abs().powf(-2.33 - 1.45 * (wind_speed * w_p / gravity - 1.17))
+    }
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+fn mitsuyasu_shaping(w: f32, w_p: f32, wind_speed: f32, gravity: f32) -> f32 {
+    let s_p = 11.5 * (gravity / (w_p * wind_speed)).powf(2.5);
+    s_p * (w / w_p).powf(if w <= w_p { 5.0 } else { -2.5 })
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+fn donelan_banner_directional_spread(w: f32, w_p: f32, theta: f32) -> f32 {
+    let p = w / w_p;
+    let beta_s = if p < 0.95 {
+        2.61 * p.powf(1.3)
+    } else if p < 1.6 {
+        2.28 * p.powf(-1.3)
+    } else {
+        10f32.powf(-0.4 + 0.8393 * (-0.567 * (p * p).ln()).exp())
+    };
+    let sech = 1.0 / (beta_s * theta).cosh();
+    beta_s / (2.0 * (beta_s * PI).tanh()) * sech * sech
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+fn positive_cosine_squared_directional_spread(theta: f32) -> f32 {
+    let c = theta.cos();
+    if theta.abs() < 0.5 * PI { 2.0 / PI * c * c } else { 0.0 }
+}
+
+// Source: Jerry Tessendorf - Simulating Ocean Water, rewritten in terms of w for deep water
+fn phillips_spectrum(w: f32, wind_speed: f32, alpha: f32, gravity: f32) -> f32 {
+    let w_0 = gravity / wind_speed;
+    (alpha * gravity * gravity) / w.powi(5) * (-(w_0 / w).powi(4)).exp()
+}
+
+// Source: https://wikiwaves.org/Ocean-Wave_Spectra#Pierson-Moskowitz_Spectrum
+fn pierson_moskowitz_spectrum(w: f32, w_p: f32, alpha: f32, gravity: f32) -> f32 {
+    (alpha * gravity * gravity) / w.powi(5) * (-1.25 * (w_p / w).powi(4)).exp()
+}
+
+/// Returns the peak enhancement exponent of JONSWAP-like spectra.
+fn peak_enhancement_exponent(w: f32, w_p: f32, sigma: Vector2) -> f32 {
+    let s = if w <= w_p { sigma.x } else { sigma.y };
+    (-(w - w_p) * (w - w_p) / (2.0 * s * s * w_p * w_p)).exp()
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+fn jonswap_spectrum(w: f32, w_p: f32, alpha: f32, gamma: f32, beta: f32, sigma: Vector2, gravity: f32) -> f32 {
+    let r = peak_enhancement_exponent(w, w_p, sigma);
+    (alpha * gravity * gravity) / w.powi(5) * (-beta * (w_p / w).powi(4)).exp() * gamma.powf(r)
+}
+
+// Source: Christopher J. Horvath - Empirical Directional Wave Spectra for Computer Graphics
+fn kitaigorodskii_depth_attenuation(w: f32, depth: f32, gravity: f32) -> f32 {
+    let w_h = (w * (depth / gravity).sqrt()).min(2.0);
+    if w_h <= 1.0 { 0.5 * w_h * w_h } else { 1.0 - 0.5 * (2.0 - w_h) * (2.0 - w_h) }
+}
+
+// Source: Donelan, Hamilton & Hui - Directional Spectra of Wind-Generated Waves
+fn donelan_banner_spectrum(w: f32, w_p: f32, alpha: f32, gamma: f32, sigma: Vector2, gravity: f32) -> f32 {
+    let r = peak_enhancement_exponent(w, w_p, sigma);
+    (alpha * gravity * gravity) / (w.powi(4) * w_p) * (-(w_p / w).powi(4)).exp() * gamma.powf(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pierson_moskowitz_settings() -> SpectrumSettings {
+        SpectrumSettings {
+            seed: Vector2i::ZERO,
+            tile_length: Vector2::splat(50.0),
+            model: SpectrumModel::PiersonMoskowitz,
+            alpha: 8.1e-3,
+            peak_frequency: 0.8,
+            wind_speed: 10.0,
+            angle: 0.0,
+            swell: 0.0,
+            detail: 1.0,
+            spread: 0.0,
+            depth: 20.0,
+            gravity: 9.81,
+            gamma: 1.0,
+            beta: 1.25,
+            sigma: Vector2::new(0.07, 0.09),
+            spreading: DirectionalSpreading::Hasselmann,
+            spreading_table: [0.0; SPREADING_TABLE_SIZE],
+            swell_height: 0.0,
+            swell_peak_frequency: 0.6,
+            swell_angle: 0.0,
+            swell_spreading: 0.0,
+            wavenumber_cutoff: Vector2::new(0.0, f32::MAX),
+        }
+    }
+
+    #[test]
+    fn push_constant_matches_spectrum_compute_layout() {
+        // Offsets in the PushConstants block of spectrum_compute.glsl
+        let settings = SpectrumSettings {
+            sigma: Vector2::new(0.06, 0.1),
+            wavenumber_cutoff: Vector2::new(0.5, 8.0),
+            ..pierson_moskowitz_settings()
+        };
+        let bytes = settings.push_constant(3, 5).finish().unwrap();
+        let read = |offset: usize| -> [u8; 4] { bytes[offset..offset + 4].try_into().unwrap() };
+        assert_eq!(u32::from_le_bytes(read(48)), 3); // cascade_index
+        assert_eq!(f32::from_le_bytes(read(72)), 0.06); // sigma
+        assert_eq!(f32::from_le_bytes(read(76)), 0.1);
+        assert_eq!(f32::from_le_bytes(read(96)), 0.5); // wavenumber_cutoff
+        assert_eq!(f32::from_le_bytes(read(100)), 8.0);
+        assert_eq!(u32::from_le_bytes(read(104)), 5); // spectrum_layer
+        assert_eq!(bytes.len(), 112);
+    }
+
+    #[test]
+    fn spectral_moment_matches_closed_form() {
+        let settings = pierson_moskowitz_settings();
+        // Pierson-Moskowitz: m0 = alpha g² / (5 w_p⁴)
+        let m0 = 8.1e-3 * 9.81 * 9.81 / (5.0 * 0.8f32.powi(4));
+        assert!((settings.spectral_moment(0) / m0 - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn statistics_match_closed_form() {
+        let settings = SpectrumSettings {
+            angle: 30f32.to_radians(),
+            spreading: DirectionalSpreading::PositiveCosineSquared,
+            ..pierson_moskowitz_settings()
+        };
+        let statistics = SpectralStatistics::new(&[settings], false);
+        // Pierson-Moskowitz: m0 = alpha g² / (5 w_p⁴), m2 = alpha g² sqrt(PI) / (4 sqrt(1.25) w_p²)
+        let m0 = 8.1e-3 * 9.81 * 9.81 / (5.0 * 0.8f32.powi(4));
+        let m2 = 8.1e-3 * 9.81 * 9.81 * PI.sqrt() / (4.0 * 1.25f32.sqrt() * 0.8 * 0.8);
+        assert!((statistics.significant_wave_height / (4.0 * m0.sqrt()) - 1.0).abs() < 1e-2);
+        assert!((statistics.zero_crossing_period / (2.0 * PI * (m0 / m2).sqrt()) - 1.0).abs() < 1e-2);
+        assert!((statistics.peak_period / (2.0 * PI / 0.8) - 1.0).abs() < 1e-2);
+        assert!((statistics.mean_direction - 30.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn swell_is_depth_attenuated_only_with_tma() {
+        let deep = SpectrumSettings { swell_height: 2.0, depth: 1000.0, ..pierson_moskowitz_settings() };
+        let shallow = SpectrumSettings { depth: 1.0, ..deep };
+        assert_eq!(shallow.swell_spectrum(0.6), deep.swell_spectrum(0.6));
+        let shallow_tma = SpectrumSettings { model: SpectrumModel::Tma, ..shallow };
+        assert!(shallow_tma.swell_spectrum(0.6) < 0.5 * deep.swell_spectrum(0.6));
+    }
+
+    #[test]
+    fn single_cascade_covers_every_wavenumber() {
+        assert_eq!(wavenumber_cutoffs(&[Vector2::new(50.0, 50.0)], &[256]), vec![Vector2::new(0.0, f32::MAX)]);
+    }
+
+    #[test]
+    fn cascades_share_boundaries_in_any_order() {
+        let tile_lengths = [Vector2::new(20.0, 20.0), Vector2::new(250.0, 250.0), Vector2::new(60.0, 40.0)];
+        let cutoffs = wavenumber_cutoffs(&tile_lengths, &[256; 3]);
+        // Largest to smallest: 250 -> 60 -> 20
+        assert_eq!(cutoffs[1].x, 0.0);
+        assert_eq!(cutoffs[1].y, cutoffs[2].x);
+        assert_eq!(cutoffs[2].y, cutoffs[0].x);
+        assert_eq!(cutoffs[0].y, f32::MAX);
+        assert!((cutoffs[0].x - 6.0 * 2.0 * PI / 20.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn boundary_never_exceeds_nyquist_of_larger_cascade() {
+        let cutoffs = wavenumber_cutoffs(&[Vector2::new(1000.0, 1000.0), Vector2::new(5.0, 5.0)], &[64, 256]);
+        assert_eq!(cutoffs[0].y, PI * 64.0 / 1000.0);
+        assert_eq!(cutoffs[1].x, cutoffs[0].y);
+    }
+}
@@ -1,6 +1,44 @@
 use godot::classes::class_macros::registry::signal;
 use godot::prelude::*;
-use godot::classes::Resource;
+use godot::classes::{Curve, Resource};
+use crate::spectrum::{SpectralStatistics, SpectrumSettings, DEFAULT_JONSWAP_BETA, DEFAULT_JONSWAP_GAMMA, DEFAULT_JONSWAP_SIGMA};
+use crate::wave_generator::{supported_map_size, G};
+
+/// Omnidirectional wave spectrum used to generate a cascade. The discriminants match the
+/// `SPECTRUM_*` defines in `spectrum_compute.glsl`.
+#[derive(GodotConvert, Var, Export, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[godot(via = i32)]
+pub enum SpectrumModel {
+    /// Tessendorf's Phillips spectrum. Scaled by `phillips_amplitude`, ignores fetch.
+    Phillips = 0,
+    /// Fully developed sea. Depends on wind speed only.
+    PiersonMoskowitz = 1,
+    /// Fetch-limited sea.
+    Jonswap = 2,
+    /// JONSWAP with depth attenuation.
+    #[default]
+    Tma = 3,
+    /// Fetch-limited sea with a peak that sharpens as the sea develops.
+    DonelanBanner = 4,
+}
+
+/// Directional spreading function used to distribute a cascade's spectrum around the wind
+/// direction. The discriminants match the `SPREADING_*` defines in `spectrum_compute.glsl`.
+#[derive(GodotConvert, Var, Export, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[godot(via = i32)]
+pub enum DirectionalSpreading {
+    /// Frequency dependent cos-2s spreading fitted by Hasselmann et al.
+    #[default]
+    Hasselmann = 0,
+    /// Frequency dependent cos-2s spreading fitted by Mitsuyasu et al.
+    Mitsuyasu = 1,
+    /// Frequency dependent sech² spreading. Keeps some energy travelling against the wind.
+    DonelanBanner = 2,
+    /// Frequency independent cos² spreading over the half plane facing the wind.
+    PositiveCosineSquared = 3,
+    /// Samples `custom_spreading`.
+    Custom = 4,
+}
 #[derive(GodotClass)]
 #[class(base=Resource, tool)]
 pub struct WaveCascadeParameters {
@@ -8,7 +46,40 @@ pub struct WaveCascadeParameters {
     // #[var] shows it to the code
     // #[func] shows a function to the code
     #[export]
+    #[var(get, set = set_tile_length)]
     pub tile_length: Vector2,
+    /// Resolution of this cascade's spectrum and FFT. 0 uses `Ocean.map_size`. Cascades below the
+    /// largest resolution are upsampled into the shared maps. This saves spectrum and FFT work and
+    /// FFT buffer memory, but the textures and the unpacking into the maps stay at the largest size.
+    #[export(enum = (Ocean = 0, _128x128 = 128, _256x256 = 256, _512x512 = 512, _1024x1024 = 1024, _2048x2048 = 2048, _4096x4096 = 4096))]
+    #[var(set = set_map_size)]
+    pub map_size: i32,
+    /// How often this cascade is updated, in Hz. 0 updates it on every ocean update. Long swell
+    /// changes slowly and can update far less often than short ripples.
+    #[export(range = (0.0, 60.0, 0.1, or_greater))]
+    pub update_rate: real,
+    #[export]
+    #[var(set = set_spectrum_model)]
+    pub spectrum_model: SpectrumModel,
+    #[export(range = (0.0, 0.1, 0.0001, or_greater))]
+    #[var(set = set_phillips_amplitude)]
+    pub phillips_amplitude: real,
+    /// Peak enhancement factor of JONSWAP and TMA. 1 gives a Pierson-Moskowitz shaped peak.
+    #[export(range = (1.0, 10.0, 0.01, or_greater))]
+    #[var(set = set_jonswap_gamma)]
+    pub jonswap_gamma: real,
+    /// Peak width of JONSWAP and TMA below the peak frequency.
+    #[export(range = (0.001, 0.5, 0.001))]
+    #[var(set = set_jonswap_sigma_below)]
+    pub jonswap_sigma_below: real,
+    /// Peak width of JONSWAP and TMA above the peak frequency.
+    #[export(range = (0.001, 0.5, 0.001))]
+    #[var(set = set_jonswap_sigma_above)]
+    pub jonswap_sigma_above: real,
+    /// How sharply JONSWAP and TMA fall off below the peak frequency.
+    #[export(range = (0.0, 5.0, 0.01))]
+    #[var(set = set_jonswap_beta)]
+    pub jonswap_beta: real,
     #[export(range = (0.0, 2.0))]
     pub displacement_scale: real,
     #[export(range = (0.0, 2.0))]
@@ -19,10 +90,38 @@ pub struct WaveCascadeParameters {
     pub wind_direction: real,
     #[export(range = (0.0001, 1000.0, or_greater))]
     pub fetch_length: real,
+    /// Water depth in m. Shallow water slows long waves down and, with TMA, flattens them.
+    #[export(range = (0.1, 1000.0, 0.1, or_greater))]
+    #[var(set = set_depth)]
+    pub depth: real,
     #[export(range = (0.0,2.0))]
     pub swell: real,
+    /// Significant wave height of a swell arriving from elsewhere, in m. 0 disables the swell.
+    #[export(range = (0.0, 10.0, 0.01, or_greater))]
+    #[var(set = set_swell_height)]
+    pub swell_height: real,
+    /// Peak period of the swell, in s.
+    #[export(range = (1.0, 25.0, 0.1, or_greater))]
+    #[var(set = set_swell_period)]
+    pub swell_period: real,
+    /// Direction the swell travels towards, independent of `wind_direction`.
+    #[export(range = (-360.0, 360.0))]
+    #[var(set = set_swell_direction)]
+    pub swell_direction: real,
+    /// Directional standard deviation of the swell, in degrees.
+    #[export(range = (1.0, 90.0, 0.1))]
+    #[var(set = set_swell_spread)]
+    pub swell_spread: real,
     #[export(range = (0.0,1.0))]
     pub spread: real,
+    #[export]
+    #[var(set = set_directional_spreading)]
+    pub directional_spreading: DirectionalSpreading,
+    /// Spreading used by `DirectionalSpreading::Custom`, sampled across its domain from the wind
+    /// direction to the opposite direction. It is normalized so that the cascade keeps its energy.
+    #[export]
+    #[var(set = set_custom_spreading)]
+    pub custom_spreading: Option<Gd<Curve>>,
     #[export(range = (0.0,1.0))]
     pub detail: real,
     #[export(range = (0.0,2.0))]
@@ -32,6 +131,12 @@ pub struct WaveCascadeParameters {
     pub spectrum_seed: Vector2i,
     pub should_generate_spectrum: bool,
     pub time: real,
+    /// Set from `Ocean::gravity`.
+    pub gravity: real,
+    /// Set from `Ocean::loop_period`.
+    pub loop_period: real,
+    /// Band of wavenumbers this cascade generates, set by `Ocean` from the other cascades.
+    pub wavenumber_cutoff: Vector2,
     pub foam_grow_rate: real,
     pub foam_decay_rate: real,
     base: Base<Resource>
@@ -42,18 +147,36 @@ impl IResource for WaveCascadeParameters {
         // godot_print!("Wave cascade parameters initialized");
         Self {
             tile_length: Vector2::new(50.0, 50.0),
+            map_size: 0,
+            update_rate: 0.0,
+            spectrum_model: SpectrumModel::default(),
+            phillips_amplitude: 8.1e-3,
+            jonswap_gamma: DEFAULT_JONSWAP_GAMMA,
+            jonswap_sigma_below: DEFAULT_JONSWAP_SIGMA.0,
+            jonswap_sigma_above: DEFAULT_JONSWAP_SIGMA.1,
+            jonswap_beta: DEFAULT_JONSWAP_BETA,
             displacement_scale: 1.0,
             normal_scale: 1.0,
             wind_speed: 20.0,
             wind_direction: 0.0,
             fetch_length: 550.0,
+            depth: 20.0,
             swell: 0.8,
+            swell_height: 0.0,
+            swell_period: 10.0,
+            swell_direction: 0.0,
+            swell_spread: 15.0,
             spread: 0.2,
+            directional_spreading: DirectionalSpreading::default(),
+            custom_spreading: None,
             detail: 1.0,
             whitecap: 0.5,
             foam_amount: 5.0,
             spectrum_seed: Vector2i { x: 0, y: 0 },
             time: 0.0,
+            gravity: G,
+            loop_period: 0.0,
+            wavenumber_cutoff: Vector2::new(0.0, real::MAX),
             foam_decay_rate: 0.0,
             foam_grow_rate: 0.0,
             should_generate_spectrum: true,
@@ -63,6 +186,159 @@ impl IResource for WaveCascadeParameters {
 }
 #[godot_api]
 impl WaveCascadeParameters {
+    /// Emitted when `tile_length` changes, which moves the cascade bands and map scales. Deferred,
+    /// so handlers can bind the parameters again.
     #[signal]
     pub fn scale_changed();
+
+    /// Emitted when `map_size` changes, which needs new GPU resources. Deferred, so handlers can
+    /// bind the parameters again.
+    #[signal]
+    pub fn map_size_changed();
+
+    #[func]
+    pub fn set_tile_length(&mut self, value: Vector2) {
+        if value == self.tile_length {
+            return;
+        }
+        self.tile_length = value;
+        self.should_generate_spectrum = true;
+        self.base_mut().call_deferred("emit_signal", &["scale_changed".to_variant()]);
+    }
+
+    #[func]
+    pub fn set_map_size(&mut self, value: i32) {
+        // 0 uses the ocean's map size
+        let value = if value == 0 { 0 } else { validated_map_size("map_size", value) };
+        if value == self.map_size {
+            return;
+        }
+        self.map_size = value;
+        self.should_generate_spectrum = true;
+        // Emitting right away would run the handlers while this resource is still bound
+        self.base_mut().call_deferred("emit_signal", &["map_size_changed".to_variant()]);
+    }
+
+    #[func]
+    pub fn set_spectrum_model(&mut self, value: SpectrumModel) {
+        self.spectrum_model = value;
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_phillips_amplitude(&mut self, value: real) {
+        self.phillips_amplitude = validated("phillips_amplitude", value, 0.0, real::MAX);
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_depth(&mut self, value: real) {
+        self.depth = validated("depth", value, 0.1, real::MAX);
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_swell_height(&mut self, value: real) {
+        self.swell_height = validated("swell_height", value, 0.0, real::MAX);
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_swell_period(&mut self, value: real) {
+        self.swell_period = validated("swell_period", value, 1.0, real::MAX);
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_swell_direction(&mut self, value: real) {
+        self.swell_direction = value;
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_swell_spread(&mut self, value: real) {
+        self.swell_spread = validated("swell_spread", value, 1.0, 90.0);
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_directional_spreading(&mut self, value: DirectionalSpreading) {
+        self.directional_spreading = value;
+        self.should_generate_spectrum = true;
+    }
+
+    /// Also regenerates the spectrum whenever the points of the curve are edited.
+    #[func]
+    pub fn set_custom_spreading(&mut self, value: Option<Gd<Curve>>) {
+        let callable = self.base().callable("mark_spectrum_stale");
+        if let Some(mut old) = self.custom_spreading.take() {
+            if old.is_connected("changed", &callable) {
+                old.disconnect("changed", &callable);
+            }
+        }
+        if let Some(mut curve) = value.clone() {
+            curve.connect("changed", &callable);
+        }
+        self.custom_spreading = value;
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    fn mark_spectrum_stale(&mut self) {
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_jonswap_gamma(&mut self, value: real) {
+        self.jonswap_gamma = validated("jonswap_gamma", value, 1.0, real::MAX);
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_jonswap_sigma_below(&mut self, value: real) {
+        self.jonswap_sigma_below = validated("jonswap_sigma_below", value, 0.001, 0.5);
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_jonswap_sigma_above(&mut self, value: real) {
+        self.jonswap_sigma_above = validated("jonswap_sigma_above", value, 0.001, 0.5);
+        self.should_generate_spectrum = true;
+    }
+
+    #[func]
+    pub fn set_jonswap_beta(&mut self, value: real) {
+        self.jonswap_beta = validated("jonswap_beta", value, 0.0, real::MAX);
+        self.should_generate_spectrum = true;
+    }
+
+    /// Returns the significant wave height, peak and zero-crossing periods, mean direction and
+    /// steepness of this cascade's whole spectrum, ignoring how it is split between cascades.
+    /// Integrates the spectrum on every call.
+    #[func]
+    pub fn get_spectral_statistics(&self) -> Dictionary {
+        SpectralStatistics::new(&[SpectrumSettings::new(self)], false).to_dictionary()
+    }
+}
+
+/// Rounds `value` to a map size the pipeline supports, warning when it had to.
+pub(crate) fn validated_map_size(property: &str, value: i32) -> i32 {
+    let supported = supported_map_size(value);
+    if supported != value {
+        godot_warn!("wave_cascade_parameters.rs: {property} is out of range, clamped {value} to {supported}");
+    }
+    supported
+}
+
+/// Clamps `value` to the range the spectrum shader can handle, warning when it had to.
+fn validated(property: &str, value: real, min: real, max: real) -> real {
+    if value.is_nan() {
+        godot_warn!("wave_cascade_parameters.rs: {property} must be a number, using {min}");
+        return min;
+    }
+    let clamped = value.clamp(min, max);
+    if clamped != value {
+        godot_warn!("wave_cascade_parameters.rs: {property} is out of range, clamped {value} to {clamped}");
+    }
+    clamped
 }
\ No newline at end of file
@@ -1,21 +1,69 @@
-use std::f32::consts::LN_2;
 use godot::classes::notify::NodeNotification;
 use godot::classes::rendering_device::{DataFormat, StorageBufferUsage, TextureUsageBits};
+use std::fmt;
+
 use godot::prelude::*;
-use godot::classes::{Node, RdTextureView, RenderingServer};
-use crate::rendering_context::{Descriptor, RenderingContext};
-use crate::wave_cascade_parameters::WaveCascadeParameters;
+use godot::classes::{Node, RdTextureView, RenderingDevice, RenderingServer};
+use crate::compute_pipeline::{ComputePipeline, DispatchError};
+use crate::push_constant::PushConstant;
+use crate::shaders::{self, EmbeddedShader};
+use crate::rendering_context::{Descriptor, DescriptorSetError, RenderingContext};
+use crate::simulation_thread::SimulationThread;
+use crate::spectrum::{spreading_table, SpectrumSettings, SPREADING_TABLE_SIZE};
+use crate::wave_cascade_parameters::{DirectionalSpreading, WaveCascadeParameters};
+
+/// Standard gravity, the default for `Ocean::gravity`.
+pub(crate) const G: f32 = 9.81;
+
+/// Largest map size whose rows fit in one work group of `fft_compute.glsl`, matching its
+/// `MAX_MAP_SIZE`. Larger maps run one `fft_stage.glsl` dispatch per FFT stage instead.
+const MAX_SINGLE_PASS_FFT_MAP_SIZE: i32 = 1024;
+/// Smallest map size `fft_butterfly.glsl` can generate factors for with its 64 wide work groups.
+const MIN_MAP_SIZE: i32 = 128;
+/// Largest map size offered by `Ocean.map_size` and `WaveCascadeParameters.map_size`.
+const MAX_MAP_SIZE: i32 = 4096;
+/// Slack for the rounding of accumulated update deltas, so a cascade due after exactly one
+/// period isn't postponed by an update.
+const SCHEDULE_EPSILON: f64 = 1e-6;
+/// Largest storage buffer `RenderingDevice` can create, as it takes the size as a u32. Offsets into
+/// the FFT buffer are u32 vec2 indices, which can't overflow below this size either.
+const MAX_STORAGE_BUFFER_SIZE: u64 = u32::MAX as u64;
+
+/// Why `WaveGenerator::init_gpu` failed.
+#[derive(Debug)]
+pub(crate) enum InitError {
+    /// A shader doesn't match the resources bound to it.
+    DescriptorSet(DescriptorSetError),
+    /// The FFT buffer of every cascade together is larger than one storage buffer can be.
+    FftBufferTooLarge { size: u64, max: u64 },
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitError::DescriptorSet(e) => write!(f, "{e}"),
+            InitError::FftBufferTooLarge { size, max } => {
+                write!(f, "The cascades need a {size} byte FFT buffer, more than the {max} bytes a storage buffer can hold; use fewer cascades or smaller map sizes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InitError {}
 
-const G: f32 = 9.81;
-const GSQ: f32 = G * G;
-const DEPTH: f32 = 20.0;
+impl From<DescriptorSetError> for InitError {
+    fn from(e: DescriptorSetError) -> Self {
+        InitError::DescriptorSet(e)
+    }
+}
 
 pub(crate) enum DESCRIPTOR {
     Spectrum = 0,
     ButterflyFactors = 1,
     FftBuffer = 2,
     DisplacementMap = 3,
-    NormalMap = 4
+    NormalMap = 4,
+    SpreadingTable = 5
 }
 
 pub(crate) enum PIPELINE {
@@ -24,7 +72,8 @@ pub(crate) enum PIPELINE {
     FftButterfly,
     FftCompute,
     Transpose,
-    FftUnpack
+    FftUnpack,
+    FftStage
 }
 
 #[derive(GodotClass)]
@@ -32,10 +81,32 @@ pub(crate) enum PIPELINE {
 pub struct WaveGenerator {
     pub(crate) map_size: i32,
     context: Option<Gd<RenderingContext>>,
-    pipelines: [Option<Callable>; 6],
-    pub(crate) descriptors: [Descriptor; 5],
-    pass_num_cascades_remaining: u32,
+    pipelines: [Option<ComputePipeline>; 7],
+    pub(crate) descriptors: [Descriptor; 6],
+    num_cascades: u32,
+    /// Map size each cascade's spectrum and FFT are computed at. `map_size` is the largest.
+    cascade_map_sizes: Vec<i32>,
+    /// Which of its two spectrum layers each cascade currently modulates.
+    spectrum_slots: Vec<u32>,
+    /// Texels of cascade maps a single `update` may process. 0 is unlimited.
+    pub(crate) frame_budget: i64,
+    /// Sum of the `update` deltas, which cascade update times are measured on.
+    clock: f64,
+    /// When each cascade was last updated on `clock`, `None` before its first update.
+    last_updates: Vec<Option<f64>>,
+    /// Decisions of the most recent `update`, returned by `get_last_schedule`.
+    last_schedule: Vec<ScheduleEntry>,
+    /// Runs the simulation on a local device on a worker thread, see `SimulationThread`. Must be
+    /// set before `init_gpu`.
+    pub(crate) threaded: bool,
+    simulation_thread: Option<SimulationThread>,
+    /// Shader files used instead of the embedded shaders, keyed by shader name. Must be set
+    /// before `init_gpu`.
+    pub(crate) shader_overrides: Dictionary,
+    pass_ocean_time: f64,
     pass_parameters: Array<Option<Gd<WaveCascadeParameters>>>,
+    pass_targets: Array<Option<Gd<WaveCascadeParameters>>>,
+    pass_transition: f32,
     base: Base<Node>
 }
 
@@ -44,62 +115,182 @@ impl INode for WaveGenerator {
     fn on_notification(&mut self, what: NodeNotification) {
         match what {
             NodeNotification::PREDELETE => {
+                // The worker must be done with the local device before its resources are freed
+                self.simulation_thread = None;
                 // Resources will be freed automatically
             }
             _ => {}
         }
     }
-    
-    fn process(&mut self, _delta: f64) {
-        // Update one cascade each frame for load balancing
-        if self.pass_num_cascades_remaining == 0 {
-            return;
-        }
-        self.pass_num_cascades_remaining -= 1;
-        let compute_list = self.context.as_mut().expect("Rendering context was null").bind_mut().compute_list_begin();
-        self._update(compute_list, self.pass_num_cascades_remaining, self.pass_parameters.clone());
-        self.context.as_mut().unwrap().bind_mut().compute_list_end();
-    }
+}
+
+/// Scheduling decision for one cascade, see `WaveGenerator::get_last_schedule`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ScheduleEntry {
+    pub priority: f32,
+    pub cost: i64,
+    pub updated: bool,
 }
 
 #[godot_api]
 impl WaveGenerator {
-    /// Begins updating wave cascades based on the provided parameters. To balance stutter,
-    /// the generator will schedule one cascade update per frame. All cascades from the
-    /// previous invocation that have not been processed yet will be updated.
-    pub fn update(&mut self, delta: f64, parameters: Array<Option<Gd<WaveCascadeParameters>>>) {
-        if parameters.len() == 0 {
+    /// Updates the wave cascades that are due based on the provided parameters. A cascade is due
+    /// once a period of its `update_rate` has passed, or on every call if it has none. Due
+    /// cascades are updated most overdue first until `frame_budget` is spent; the rest stay due
+    /// and gain priority for the next call.
+    ///
+    /// While `targets` is not empty, each cascade blends from its spectrum toward the spectrum of
+    /// the matching target by `transition`, from 0 to 1.
+    pub fn update(&mut self, delta: f64, ocean_time: f64, parameters: Array<Option<Gd<WaveCascadeParameters>>>, targets: Array<Option<Gd<WaveCascadeParameters>>>, transition: f32) {
+        // A threaded simulation still in flight skips this update, so its cascades grow overdue
+        #[cfg(feature = "simulation-thread")]
+        if self.simulation_thread.as_mut().is_some_and(|thread| !thread.collect(false)) {
+            self.clock += delta;
             return;
         }
-        if self.context == None {
-            self.init_gpu(2.max(parameters.len() as u32));
-        } else if self.pass_num_cascades_remaining != 0 {
-            // Update cascades from previous invocation that have yet to be processed...
-            let compute_list = self.context.as_mut().unwrap().bind_mut().compute_list_begin();
-            for i in 0..self.pass_num_cascades_remaining {
-                self._update(compute_list, i, self.pass_parameters.clone());
-            }
-            self.context.as_mut().unwrap().bind_mut().compute_list_end();
+        if !self.begin_update(delta, ocean_time, parameters, targets, transition) {
+            return;
         }
-        
-        // Update each cascade's parameters that rely on time delta
-        for i in 0..parameters.len() {
-            match parameters.at(i) {
-                Some(mut params_gd) => {
-                    let mut params = params_gd.bind_mut();
-                    params.time += delta as f32;
-                    // Note: The constants are used to normalize parameters between 0 and 10.
-                    params.foam_grow_rate = delta as f32 * params.foam_amount * 7.5;
-                    params.foam_decay_rate = delta as f32 * (0.5f32.max(10.0 - params.foam_amount) * 1.15);
-                }
-                None => {
-                    return;
-                }
+        let num_cascades = self.pass_parameters.len();
+        let priorities: Vec<f32> = (0..num_cascades).map(|i| self.cascade_priority(i, delta)).collect();
+        let costs: Vec<i64> = (0..num_cascades).map(|i| (self.cascade_map_size(i as u32) as i64).pow(2)).collect();
+        let cascades = schedule_cascades(&priorities, &costs, self.frame_budget);
+        self.last_schedule = (0..num_cascades)
+            .map(|i| ScheduleEntry { priority: priorities[i], cost: costs[i], updated: cascades.contains(&i) })
+            .collect();
+        self.dispatch_cascades(&cascades);
+    }
+
+    /// Updates every cascade right away, regardless of update rates and budget.
+    pub(crate) fn update_immediately(&mut self, delta: f64, ocean_time: f64, parameters: Array<Option<Gd<WaveCascadeParameters>>>) {
+        if !self.begin_update(delta, ocean_time, parameters, Array::new(), 0.0) {
+            return;
+        }
+        let cascades: Vec<usize> = (0..self.pass_parameters.len()).collect();
+        self.dispatch_cascades(&cascades);
+    }
+
+    /// Makes every cascade modulate the spectrum it was transitioning to from now on.
+    pub(crate) fn finish_transition(&mut self) {
+        // Cascades that were not updated during the transition have yet to generate its spectrum
+        let pending: Vec<usize> = self.pass_targets.iter_shared()
+            .enumerate()
+            .filter(|(_, target)| target.as_ref().is_some_and(|target| target.bind().should_generate_spectrum))
+            .map(|(i, _)| i)
+            .collect();
+        if !pending.is_empty() && self.context.is_some() {
+            let (parameters, targets) = (self.pass_parameters.clone(), self.pass_targets.clone());
+            self.upload_spreading_tables(&parameters, &targets);
+        }
+        self.dispatch_cascades(&pending);
+        for slot in self.spectrum_slots.iter_mut() {
+            *slot ^= 1;
+        }
+        self.pass_targets = Array::new();
+        self.pass_transition = 0.0;
+    }
+
+    /// Returns what the most recent `update` decided for each cascade, for debugging: its
+    /// `priority` (update periods since its last update, infinite when its spectrum must be
+    /// generated), its `cost` in texels, and whether it was `updated`.
+    #[func]
+    pub fn get_last_schedule(&self) -> Array<Dictionary> {
+        self.last_schedule.iter()
+            .enumerate()
+            .map(|(i, entry)| vdict! {
+                "cascade": i as i64,
+                "priority": entry.priority,
+                "cost": entry.cost,
+                "updated": entry.updated,
+            })
+            .collect()
+    }
+
+    /// Sets up the GPU if needed and stores the state shared by the cascade updates of this call.
+    /// Returns false if there is nothing to update.
+    fn begin_update(&mut self, delta: f64, ocean_time: f64, parameters: Array<Option<Gd<WaveCascadeParameters>>>, targets: Array<Option<Gd<WaveCascadeParameters>>>, transition: f32) -> bool {
+        if parameters.len() == 0 || parameters.iter_shared().any(|params| params.is_none()) {
+            return false;
+        }
+        if self.context == None {
+            if let Err(e) = self.init_gpu(PackedInt32Array::from(vec![self.map_size; 2.max(parameters.len())].as_slice())) {
+                godot_error!("wave_generator.rs: {e}");
+                self.context = None;
+                return false;
             }
         }
+        self.upload_spreading_tables(&parameters, &targets);
         
+        self.clock += delta;
+        self.last_updates.resize(parameters.len(), None);
+        self.pass_ocean_time = ocean_time;
         self.pass_parameters = parameters;
-        self.pass_num_cascades_remaining = self.pass_parameters.len() as u32;
+        self.pass_targets = targets;
+        self.pass_transition = transition;
+        true
+    }
+
+    /// Number of update periods since a cascade was last updated. Cascades without an
+    /// `update_rate` have a period of `delta`.
+    fn cascade_priority(&self, cascade_index: usize, delta: f64) -> f32 {
+        let Some(last_update) = self.last_updates[cascade_index] else {
+            return f32::INFINITY;
+        };
+        let params = self.pass_parameters.at(cascade_index).unwrap();
+        let params = params.bind();
+        let target_pending = self.pass_targets.get(cascade_index).flatten().is_some_and(|target| target.bind().should_generate_spectrum);
+        if params.should_generate_spectrum || target_pending {
+            return f32::INFINITY;
+        }
+        let period = if params.update_rate > 0.0 { 1.0 / params.update_rate as f64 } else { delta.max(SCHEDULE_EPSILON) };
+        ((self.clock - last_update + SCHEDULE_EPSILON) / period) as f32
+    }
+
+    /// Advances the given cascades to the ocean time of this call and updates them in one
+    /// compute list.
+    fn dispatch_cascades(&mut self, cascades: &[usize]) {
+        if cascades.is_empty() || self.context.is_none() {
+            return;
+        }
+        let compute_list = self.compute_list_begin();
+        for &i in cascades {
+            let Some(mut params) = self.pass_parameters.at(i) else {
+                continue;
+            };
+            let elapsed = self.last_updates[i].map_or(0.0, |last_update| self.clock - last_update);
+            advance_cascade(&mut params.bind_mut(), i, elapsed, self.pass_ocean_time);
+            self.last_updates[i] = Some(self.clock);
+            self._update(compute_list, i as u32, self.pass_parameters.clone());
+        }
+        self.compute_list_end();
+    }
+
+    /// Begins recording compute work. In threaded mode the work is added to the simulation thread's
+    /// next job instead, and the returned compute list is unused.
+    fn compute_list_begin(&mut self) -> i64 {
+        if self.simulation_thread.is_some() {
+            return 0;
+        }
+        self.context.as_mut().expect("Context was None").bind_mut().compute_list_begin()
+    }
+
+    /// Ends recording, which submits the job to the simulation thread in threaded mode.
+    fn compute_list_end(&mut self) {
+        #[cfg(feature = "simulation-thread")]
+        if let Some(thread) = self.simulation_thread.as_mut() {
+            thread.submit();
+            return;
+        }
+        self.context.as_mut().expect("Context was None").bind_mut().compute_list_end();
+    }
+
+    fn compute_list_add_barrier(&mut self, compute_list: i64) {
+        #[cfg(feature = "simulation-thread")]
+        if let Some(thread) = self.simulation_thread.as_mut() {
+            thread.barrier();
+            return;
+        }
+        self.context.as_mut().expect("Context was None").bind_mut().compute_list_add_buffer(compute_list);
     }
     
     #[func]
@@ -109,133 +300,210 @@ impl WaveGenerator {
             None => return,
         };
         let mut params = params_gd.bind_mut();
+        let slot = self.spectrum_slots[cascade_index as usize];
+        let spectrum_layer = self.spectrum_layer(cascade_index, slot);
+        let mut target_spectrum_layer = spectrum_layer;
+        let mut transition = 0.0;
         
         // Wave spectra update
         if params.should_generate_spectrum {
-            let alpha = jonswap_alpha(params.get_wind_speed(), params.get_fetch_length() * 1e3);
-            let omega = jonswap_peak_angular_frequency(params.get_wind_speed(), params.get_fetch_length() * 1e3);
-            let push_constant = RenderingContext::create_push_constant(&[
-                params.spectrum_seed.x.to_variant(),
-                params.spectrum_seed.y.to_variant(),
-                params.tile_length.x.to_variant(),
-                params.tile_length.y.to_variant(),
-                alpha.to_variant(),
-                omega.to_variant(),
-                params.wind_speed.to_variant(), 
-                params.wind_direction.to_radians().to_variant(), 
-                DEPTH.to_variant(), 
-                params.swell.to_variant(), 
-                params.detail.to_variant(), 
-                params.spread.to_variant(), 
-                cascade_index.to_variant()
-            ]);
-            self.pipelines[PIPELINE::SpectrumCompute as usize].as_mut().expect("Spectrum compute pipeline was None").call(
-                &[
-                    self.context.as_mut().expect("Context was None").to_variant(), 
-                    compute_list.to_variant(), 
-                    push_constant.to_variant(),
-                ]
-            );
+            if !self.generate_spectrum(compute_list, SpectrumSettings::new(&params), cascade_index, spectrum_layer) {
+                return;
+            }
             params.should_generate_spectrum = false;
         }
+        if let Some(mut target_gd) = self.pass_targets.get(cascade_index as usize).flatten() {
+            let mut target = target_gd.bind_mut();
+            target_spectrum_layer = self.spectrum_layer(cascade_index, slot ^ 1);
+            transition = self.pass_transition;
+            if target.should_generate_spectrum {
+                if !self.generate_spectrum(compute_list, SpectrumSettings::new(&target), cascade_index, target_spectrum_layer) {
+                    return;
+                }
+                target.should_generate_spectrum = false;
+            }
+        }
         
-        self.pipelines[PIPELINE::SpectrumModulate as usize].as_mut().expect("Spectrum modulate pipeline was None").call(
-            &[
-                self.context.as_mut().expect("Context was None").to_variant(), 
-                compute_list.to_variant(), 
-                RenderingContext::create_push_constant(&[
-                    params.tile_length.x.to_variant(), 
-                    params.tile_length.y.to_variant(), 
-                    DEPTH.to_variant(), 
-                    params.time.to_variant(), 
-                    cascade_index.to_variant()
-                ]).to_variant()
-            ]
-        );
+        let map_size = self.cascade_map_size(cascade_index);
+        let data_offset = self.fft_data_offset(cascade_index);
+        let spectrum_dispatch = (map_size / 16).max(1);
+        if !self.dispatch(PIPELINE::SpectrumModulate, compute_list, PushConstant::new()
+            .push_vec2(params.tile_length)
+            .push_f32(params.depth)
+            .push_f32(params.time)
+            .push_u32(data_offset)
+            .push_f32(params.gravity)
+            .push_u32(spectrum_layer)
+            .push_u32(target_spectrum_layer)
+            .push_f32(transition)
+            .push_f32(params.loop_period),
+            [spectrum_dispatch, spectrum_dispatch, 1]
+        ) {
+            return;
+        }
         
         // --- WAVE SPECTRA INVERSE FOURIER TRANSFORM ---
-        let fft_push_constant = RenderingContext::create_push_constant(&[cascade_index.to_variant()]);
         // Note: We need not do a second transpose after computing FFT on rows since rotating the wave by
         // PI/2 doesn't affect it visually.
-        self.pipelines[PIPELINE::FftCompute as usize].as_mut().expect("FFT Compute pipeline was None").call(&[
-            self.context.as_mut().expect("Context was None").to_variant(), 
-            compute_list.to_variant(), 
-            fft_push_constant.to_variant()
-        ]);
-        self.pipelines[PIPELINE::Transpose as usize].as_mut().expect("Transpose pipeline was None").call(&[
-            self.context.as_mut().expect("Context was None").to_variant(), 
-            compute_list.to_variant(), 
-            fft_push_constant.to_variant()
-        ]);
-        self.context.as_mut().expect("Context was None").bind_mut().compute_list_add_buffer(compute_list);
-        self.pipelines[PIPELINE::FftCompute as usize].as_mut().expect("FFT Compute pipeline was None").call(&[
-            self.context.as_mut().expect("Context was None").to_variant(), 
-            compute_list.to_variant(), 
-            fft_push_constant.to_variant()
-        ]);
+        self.fft_rows(compute_list, cascade_index);
+        let transpose_dispatch = (map_size / 32).max(1);
+        self.dispatch(PIPELINE::Transpose, compute_list, PushConstant::new().push_u32(data_offset), [transpose_dispatch, transpose_dispatch, 4]);
+        self.compute_list_add_barrier(compute_list);
+        self.fft_rows(compute_list, cascade_index);
 
         // ## --- DISPLACEMENT/NORMAL MAP UPDATE ---
-        let fft_unpack_push_constant = RenderingContext::create_push_constant(&[
-            cascade_index.to_variant(), 
-            params.whitecap.to_variant(), 
-            params.foam_grow_rate.to_variant(), 
-            params.foam_decay_rate.to_variant()
-        ]);
-        self.pipelines[PIPELINE::FftUnpack as usize].as_mut().expect("FFT Unpack pipeline was None").call(&[
-            self.context.as_mut().unwrap().to_variant(),
-            compute_list.to_variant(),
-            fft_unpack_push_constant.to_variant()
-        ]);
+        // Runs at the size of the output maps, upsampling cascades with a smaller map size
+        let unpack_dispatch = (self.map_size / 16).max(1);
+        self.dispatch(PIPELINE::FftUnpack, compute_list, PushConstant::new()
+            .push_u32(cascade_index)
+            .push_f32(params.whitecap)
+            .push_f32(params.foam_grow_rate)
+            .push_f32(params.foam_decay_rate)
+            .push_u32(data_offset)
+            .push_u32(map_size as u32),
+            [unpack_dispatch, unpack_dispatch, 1]
+        );
+    }
+
+    /// Transforms every row of a cascade's spectra from the input to the output half of the FFT buffer.
+    fn fft_rows(&mut self, compute_list: i64, cascade_index: u32) {
+        let map_size = self.cascade_map_size(cascade_index);
+        let data_offset = self.fft_data_offset(cascade_index);
+        let butterfly_offset = butterfly_offset(map_size);
+        if map_size <= MAX_SINGLE_PASS_FFT_MAP_SIZE {
+            self.dispatch(PIPELINE::FftCompute, compute_list, PushConstant::new()
+                .push_u32(data_offset)
+                .push_u32(butterfly_offset),
+                [1, map_size, 4]
+            );
+            return;
+        }
+        // Each stage swaps halves, so an even number of stages needs one more pass to end in the output half
+        let num_fft_stages = map_size.ilog2();
+        for stage in 0..num_fft_stages + (num_fft_stages + 1) % 2 {
+            if !self.dispatch(PIPELINE::FftStage, compute_list, PushConstant::new()
+                .push_u32(data_offset)
+                .push_u32(butterfly_offset)
+                .push_u32(stage),
+                [map_size / 64, map_size, 4]
+            ) {
+                return;
+            }
+            self.compute_list_add_barrier(compute_list);
+        }
+    }
+
+    /// Dispatches `spectrum_compute.glsl` into one spectrum layer. Returns false if nothing was dispatched.
+    fn generate_spectrum(&mut self, compute_list: i64, settings: SpectrumSettings, cascade_index: u32, spectrum_layer: u32) -> bool {
+        let spectrum_dispatch = (self.cascade_map_size(cascade_index) / 16).max(1);
+        self.dispatch(PIPELINE::SpectrumCompute, compute_list, settings.push_constant(cascade_index, spectrum_layer), [spectrum_dispatch, spectrum_dispatch, 1])
+    }
+
+    /// Records `pipeline` into `compute_list`, or the next job in threaded mode, with `block_dimensions`
+    /// work groups, which lets every cascade run at its own map size. Returns false if nothing was
+    /// dispatched.
+    fn dispatch(&mut self, pipeline: PIPELINE, compute_list: i64, push_constant: PushConstant, block_dimensions: [i32; 3]) -> bool {
+        let Some(push_constant) = pack_push_constant(push_constant) else {
+            return false;
+        };
+        let groups = block_dimensions.map(|count| count.max(0) as u32);
+        let pipeline = self.pipelines[pipeline as usize].as_ref().expect("Pipeline was None");
+        #[cfg(feature = "simulation-thread")]
+        if let Some(thread) = self.simulation_thread.as_mut() {
+            return dispatched(pipeline.record_groups(&push_constant, &[], groups).map(|dispatch| thread.dispatch(dispatch)));
+        }
+        let mut context = self.context.as_mut().expect("Context was None").bind_mut();
+        dispatched(pipeline.dispatch_groups(&mut context, compute_list, &push_constant, &[], groups))
+    }
+
+    /// Each cascade owns two spectrum layers: `cascade_index` and `cascade_index + num_cascades`.
+    fn spectrum_layer(&self, cascade_index: u32, slot: u32) -> u32 {
+        cascade_index + slot * self.num_cascades
+    }
+
+    /// Map size a cascade's spectrum and FFT are computed at.
+    fn cascade_map_size(&self, cascade_index: u32) -> i32 {
+        self.cascade_map_sizes.get(cascade_index as usize).copied().unwrap_or(self.map_size)
+    }
+
+    /// Start of a cascade's data in the FFT buffer, in vec2s.
+    fn fft_data_offset(&self, cascade_index: u32) -> u32 {
+        self.cascade_map_sizes.iter()
+            .take(cascade_index as usize)
+            .map(|&map_size| fft_data_size(map_size))
+            .sum()
     }
     
-    #[func]
-    pub(crate) fn init_gpu(&mut self, num_cascades: u32) {
+    /// Creates the GPU resources for one cascade per entry of `cascade_map_sizes`. The displacement
+    /// and normal maps take the largest of the sizes. Fails if the FFT buffer of the cascades is
+    /// too large, or if a shader doesn't match the resources bound to it.
+    pub(crate) fn init_gpu(&mut self, cascade_map_sizes: PackedInt32Array) -> Result<(), InitError> {
+        let fft_buffer_size = fft_buffer_size(cascade_map_sizes.as_slice());
+        if fft_buffer_size > MAX_STORAGE_BUFFER_SIZE {
+            return Err(InitError::FftBufferTooLarge { size: fft_buffer_size, max: MAX_STORAGE_BUFFER_SIZE });
+        }
+
         // Device/Shader Creation
         if self.context == None {
+            let device = match self.start_simulation_thread() {
+                Some(device) => Some(device),
+                None => RenderingServer::singleton().get_rendering_device(),
+            };
             let mut temp_context = RenderingContext::new_gd();
-            temp_context.bind_mut().initialize(RenderingServer::singleton().get_rendering_device());
+            temp_context.bind_mut().initialize(device);
             self.context = Some(temp_context);
         }
         
-        let compute_list: i64;
+        self.cascade_map_sizes = cascade_map_sizes.to_vec();
+        self.map_size = self.cascade_map_sizes.iter().copied().max().unwrap_or(self.map_size);
+        let num_cascades = self.cascade_map_sizes.len() as u32;
+        let mut butterfly_sizes = self.cascade_map_sizes.clone();
+        butterfly_sizes.sort_unstable();
+        butterfly_sizes.dedup();
         {
             let mut context = self.context.as_mut().expect("Context was None").bind_mut();
-            let spectrum_compute_shader = context.load_shader("res://addons/gd_ocean/shaders/compute/spectrum_compute.glsl".to_string());
-            let fft_butterfly_shader = context.load_shader("res://addons/gd_ocean/shaders/compute/fft_butterfly.glsl".to_string());
-            let spectrum_modulate_shader = context.load_shader("res://addons/gd_ocean/shaders/compute/spectrum_modulate.glsl".to_string());
-            let fft_compute_shader = context.load_shader("res://addons/gd_ocean/shaders/compute/fft_compute.glsl".to_string());
-            let transpose_shader = context.load_shader("res://addons/gd_ocean/shaders/compute/transpose.glsl".to_string());
-            let fft_unpack_shader = context.load_shader("res://addons/gd_ocean/shaders/compute/fft_unpack.glsl".to_string());
+            let mut load_shader = |shader: &EmbeddedShader| {
+                let override_path = self.shader_overrides.get(shader.name).map(|path| path.to_string());
+                context.load_embedded_shader(shader, override_path)
+            };
+            let spectrum_compute_shader = load_shader(&shaders::SPECTRUM_COMPUTE);
+            let fft_butterfly_shader = load_shader(&shaders::FFT_BUTTERFLY);
+            let spectrum_modulate_shader = load_shader(&shaders::SPECTRUM_MODULATE);
+            let fft_compute_shader = load_shader(&shaders::FFT_COMPUTE);
+            let fft_stage_shader = load_shader(&shaders::FFT_STAGE);
+            let transpose_shader = load_shader(&shaders::TRANSPOSE);
+            let fft_unpack_shader = load_shader(&shaders::FFT_UNPACK);
             let dims: Vector2i = Vector2i { x: self.map_size as i32, y: self.map_size as i32 };
-            let num_fft_stages: i32 = ((self.map_size as f32).ln() / LN_2).floor() as i32;
+            self.num_cascades = num_cascades;
+            self.spectrum_slots = vec![0; num_cascades as usize];
 
             // Prepare Descriptors:
+            // Cascades with a smaller map size only use the top left of their layers
             self.descriptors[DESCRIPTOR::Spectrum as usize] = context.create_texture(
                 dims, 
                 DataFormat::R32G32B32A32_SFLOAT, 
                 TextureUsageBits::STORAGE_BIT | TextureUsageBits::CAN_COPY_FROM_BIT, 
-                num_cascades, 
+                2 * num_cascades, 
                 RdTextureView::new_gd(), 
                 Array::new()
             );
             
-            // Size: (#FFT stages * map size * sizeof(vec4))
+            // Size: (butterfly factors of every map size up to the largest * sizeof(vec4))
             self.descriptors[DESCRIPTOR::ButterflyFactors as usize] = context.create_storage_buffer(
-                (num_fft_stages * self.map_size * 4 * 4) as usize, 
+                butterfly_offset(self.map_size * 2) as usize * 4 * 4, 
                 StorageBufferUsage::DISPATCH_INDIRECT
             );
             
-            // Size: (num_cascades * map_size² * 4 FFTs * 2 temp buffers * sizeof(vec2))
-            // This matches the original: num_cascades * map_size*map_size * 4*2 * 2 * 4
+            // Note: This is 1 GiB per cascade at 4096x4096.
             self.descriptors[DESCRIPTOR::FftBuffer as usize] = context.create_storage_buffer(
-                (num_cascades as u32 * self.map_size as u32 * self.map_size as u32 * 4 * 2 * 2 * 4) as usize, 
+                fft_buffer_size as usize, 
                 StorageBufferUsage::DISPATCH_INDIRECT
             );
             
             self.descriptors[DESCRIPTOR::DisplacementMap as usize] = context.create_texture(
                 dims, 
                 DataFormat::R16G16B16A16_SFLOAT, 
-                TextureUsageBits::STORAGE_BIT | TextureUsageBits::SAMPLING_BIT | TextureUsageBits::CAN_UPDATE_BIT, 
+                TextureUsageBits::STORAGE_BIT | TextureUsageBits::SAMPLING_BIT | TextureUsageBits::CAN_UPDATE_BIT | TextureUsageBits::CAN_COPY_FROM_BIT, 
                 num_cascades, 
                 RdTextureView::new_gd(), 
                 Array::new()
@@ -244,79 +512,322 @@ impl WaveGenerator {
             self.descriptors[DESCRIPTOR::NormalMap as usize] = context.create_texture(
                 dims, 
                 DataFormat::R16G16B16A16_SFLOAT, 
-                TextureUsageBits::STORAGE_BIT | TextureUsageBits::SAMPLING_BIT | TextureUsageBits::CAN_UPDATE_BIT, 
+                TextureUsageBits::STORAGE_BIT | TextureUsageBits::SAMPLING_BIT | TextureUsageBits::CAN_UPDATE_BIT | TextureUsageBits::CAN_COPY_FROM_BIT, 
                 num_cascades, 
                 RdTextureView::new_gd(), 
                 Array::new()
             );
 
-            let spectrum_set = context.create_descriptor_set(&self.descriptors[DESCRIPTOR::Spectrum as usize], spectrum_compute_shader, 0);
-            let fft_butterfly_set = context.create_descriptor_set(&self.descriptors[DESCRIPTOR::ButterflyFactors as usize], fft_butterfly_shader, 0);
-            let fft_compute_set = context.create_descriptor_set_dual(&self.descriptors[DESCRIPTOR::ButterflyFactors as usize], &self.descriptors[DESCRIPTOR::FftBuffer as usize], fft_compute_shader, 0);
-            let fft_buffer_set = context.create_descriptor_set(&self.descriptors[DESCRIPTOR::FftBuffer as usize], spectrum_modulate_shader, 1);
-            let unpack_set = context.create_descriptor_set_dual(&self.descriptors[DESCRIPTOR::DisplacementMap as usize], &self.descriptors[DESCRIPTOR::NormalMap as usize], fft_unpack_shader, 0);
-
-            // Compute pipeline creation with proper dispatch dimension validation:
-            // For small map sizes, ensure minimum dispatch dimensions
-            let butterfly_dispatch_x = (self.map_size / 2 / 64).max(1);
-            let transpose_dispatch_x = (self.map_size / 32).max(1);
-            let transpose_dispatch_y = (self.map_size / 32).max(1);
-            let spectrum_dispatch_x = (self.map_size / 16).max(1);
-            let spectrum_dispatch_y = (self.map_size / 16).max(1);
-            
+            #[cfg(feature = "simulation-thread")]
+            if let Some(thread) = self.simulation_thread.as_mut() {
+                let maps = [DESCRIPTOR::DisplacementMap, DESCRIPTOR::NormalMap].map(|map| self.descriptors[map as usize].rid);
+                thread.share_maps(maps, self.map_size, num_cascades);
+            }
+
+            // Size: (2 spectrum layers * num_cascades * SPREADING_TABLE_SIZE * sizeof(float))
+            self.descriptors[DESCRIPTOR::SpreadingTable as usize] = context.create_storage_buffer(
+                2 * num_cascades as usize * SPREADING_TABLE_SIZE * 4,
+                StorageBufferUsage::DISPATCH_INDIRECT
+            );
+
+            let spectrum_set = context.create_descriptor_set(&self.descriptors[DESCRIPTOR::Spectrum as usize], spectrum_compute_shader, 0)?;
+            let spreading_set = context.create_descriptor_set(&self.descriptors[DESCRIPTOR::SpreadingTable as usize], spectrum_compute_shader, 1)?;
+            let fft_butterfly_set = context.create_descriptor_set(&self.descriptors[DESCRIPTOR::ButterflyFactors as usize], fft_butterfly_shader, 0)?;
+            let fft_compute_set = context.create_descriptor_set_dual(&self.descriptors[DESCRIPTOR::ButterflyFactors as usize], &self.descriptors[DESCRIPTOR::FftBuffer as usize], fft_compute_shader, 0)?;
+            let fft_buffer_set = context.create_descriptor_set(&self.descriptors[DESCRIPTOR::FftBuffer as usize], spectrum_modulate_shader, 1)?;
+            let unpack_set = context.create_descriptor_set_dual(&self.descriptors[DESCRIPTOR::DisplacementMap as usize], &self.descriptors[DESCRIPTOR::NormalMap as usize], fft_unpack_shader, 0)?;
+
+            // Every pipeline is dispatched with the work groups of the cascade it runs for, see `dispatch`.
             self.pipelines[PIPELINE::SpectrumCompute as usize] = Some(context.create_pipeline(
-                vec![spectrum_dispatch_x, spectrum_dispatch_y, 1],
-                vec![spectrum_set], 
+                [1, 1, 1],
+                vec![spectrum_set, spreading_set], 
                 spectrum_compute_shader)
             );
             self.pipelines[PIPELINE::SpectrumModulate as usize] = Some(context.create_pipeline(
-                vec![spectrum_dispatch_x, spectrum_dispatch_y, 1], 
+                [1, 1, 1], 
                 vec![spectrum_set, fft_buffer_set], 
                 spectrum_modulate_shader)
             );
             self.pipelines[PIPELINE::FftButterfly as usize] = Some(context.create_pipeline(
-                vec![butterfly_dispatch_x, num_fft_stages, 1], 
+                [1, 1, 1], 
                 vec![fft_butterfly_set], 
                 fft_butterfly_shader)
             );
+            // Rows that fit in a work group are transformed in one pass, larger ones a stage at a time.
             self.pipelines[PIPELINE::FftCompute as usize] = Some(context.create_pipeline(
-                vec![1, self.map_size, 4], 
+                [1, 1, 4], 
                 vec![fft_compute_set], 
                 fft_compute_shader)
             );
+            self.pipelines[PIPELINE::FftStage as usize] = Some(context.create_pipeline(
+                [1, 1, 4], 
+                vec![fft_compute_set], 
+                fft_stage_shader)
+            );
             self.pipelines[PIPELINE::Transpose as usize] = Some(context.create_pipeline(
-                vec![transpose_dispatch_x, transpose_dispatch_y, 4], 
+                [1, 1, 4], 
                 vec![fft_compute_set], 
                 transpose_shader)
             );
             self.pipelines[PIPELINE::FftUnpack as usize] = Some(context.create_pipeline(
-                vec![spectrum_dispatch_x, spectrum_dispatch_y, 1], 
+                [1, 1, 1], 
                 vec![unpack_set, fft_buffer_set], 
                 fft_unpack_shader)
             );
-
-            compute_list = context.compute_list_begin();
         }
         
-        // Generate butterfly factors once per map_size (like the original)
-        self.pipelines[PIPELINE::FftButterfly as usize].as_mut().unwrap().call(&[
-            self.context.clone().unwrap().to_variant(), 
-            compute_list.to_variant(),
-            PackedByteArray::new().to_variant(), // Empty push constant,
-            VariantArray::new().to_variant(),
-            Rid::Invalid.to_variant(),
-            0i64.to_variant(),
-        ]);
-        self.context.as_mut().expect("Context was none somehow").bind_mut().compute_list_end();
+        // Generate butterfly factors once for every map size in use (like the original)
+        let compute_list = self.compute_list_begin();
+        for map_size in butterfly_sizes {
+            self.dispatch(PIPELINE::FftButterfly, compute_list, PushConstant::new().push_u32(butterfly_offset(map_size)), [(map_size / 2 / 64).max(1), map_size.ilog2() as i32, 1]);
+        }
+        self.compute_list_end();
+        Ok(())
+    }
+
+    /// Uploads the custom spreading table of every cascade and transition target that is about to
+    /// regenerate its spectrum with `DirectionalSpreading::Custom`. Must be called while no compute
+    /// list is open.
+    fn upload_spreading_tables(&mut self, parameters: &Array<Option<Gd<WaveCascadeParameters>>>, targets: &Array<Option<Gd<WaveCascadeParameters>>>) {
+        let rid = self.descriptors[DESCRIPTOR::SpreadingTable as usize].rid;
+        let mut layers = Vec::new();
+        for (i, params) in parameters.iter_shared().enumerate() {
+            let slot = self.spectrum_slots.get(i).copied().unwrap_or(0);
+            layers.push((params, self.spectrum_layer(i as u32, slot)));
+            layers.push((targets.get(i).flatten(), self.spectrum_layer(i as u32, slot ^ 1)));
+        }
+        let Some(context) = self.context.as_mut() else {
+            return;
+        };
+        let mut context = context.bind_mut();
+        for (params, layer) in layers {
+            let Some(params) = params else {
+                continue;
+            };
+            let params = params.bind();
+            if !params.should_generate_spectrum || params.directional_spreading != DirectionalSpreading::Custom {
+                continue;
+            }
+            let table = spreading_table(params.custom_spreading.as_ref());
+            let data = PackedByteArray::from(table.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>().as_slice());
+            let offset = layer * (SPREADING_TABLE_SIZE * 4) as u32;
+            #[cfg(feature = "simulation-thread")]
+            if let Some(thread) = self.simulation_thread.as_mut() {
+                thread.buffer_update(rid, offset, &data);
+                continue;
+            }
+            context.buffer_update(rid, offset, &data);
+        }
+    }
+
+    /// Copies the first `num_cascades` layers of the displacement map back to the CPU. Each entry
+    /// holds the raw `R16G16B16A16_SFLOAT` texels of one cascade. This stalls until the GPU is done
+    /// with the map, so callers should only do it when they actually need the data.
+    pub(crate) fn read_displacement_layers(&mut self, num_cascades: u32) -> Vec<PackedByteArray> {
+        self.read_layers(self.descriptors[DESCRIPTOR::DisplacementMap as usize].rid, num_cascades)
+    }
+
+    /// Same as `read_displacement_layers`, for the normal map.
+    pub(crate) fn read_normal_layers(&mut self, num_cascades: u32) -> Vec<PackedByteArray> {
+        self.read_layers(self.descriptors[DESCRIPTOR::NormalMap as usize].rid, num_cascades)
+    }
+
+    fn read_layers(&mut self, rid: Rid, num_cascades: u32) -> Vec<PackedByteArray> {
+        #[cfg(feature = "simulation-thread")]
+        if let Some(thread) = self.simulation_thread.as_mut() {
+            return thread.copied_layers(rid, num_cascades);
+        }
+        match self.context.as_mut() {
+            Some(context) if rid.is_valid() => {
+                let mut context = context.bind_mut();
+                (0..num_cascades).map(|layer| context.texture_get_data(rid, layer)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The displacement or normal map materials should sample. In threaded mode this is the copy
+    /// on the main device, which lags one update behind.
+    pub(crate) fn displayed_map(&self, map: DESCRIPTOR) -> Rid {
+        let rid = self.descriptors[map as usize].rid;
+        #[cfg(feature = "simulation-thread")]
+        if let Some(thread) = self.simulation_thread.as_ref() {
+            return thread.shared_map(rid);
+        }
+        rid
+    }
+
+    /// Starts the simulation thread if `threaded` is set, and returns its local device. Clears
+    /// `threaded` if no local device could be created.
+    #[cfg(feature = "simulation-thread")]
+    fn start_simulation_thread(&mut self) -> Option<Gd<RenderingDevice>> {
+        if !self.threaded {
+            return None;
+        }
+        let Some((thread, device)) = SimulationThread::new() else {
+            godot_warn!("wave_generator.rs: No local rendering device could be created, simulating on the main thread");
+            self.threaded = false;
+            return None;
+        };
+        self.simulation_thread = Some(thread);
+        Some(device)
+    }
+
+    #[cfg(not(feature = "simulation-thread"))]
+    fn start_simulation_thread(&mut self) -> Option<Gd<RenderingDevice>> {
+        if self.threaded {
+            godot_warn!("wave_generator.rs: Built without the simulation-thread feature, simulating on the main thread");
+            self.threaded = false;
+        }
+        None
+    }
+}
+
+/// Start of the butterfly factors of `map_size` in the butterfly buffer, in vec4s. The buffer
+/// holds log2(n) x n factors for every power of two n from `MIN_MAP_SIZE` up, smallest first.
+fn butterfly_offset(map_size: i32) -> u32 {
+    (MIN_MAP_SIZE.ilog2()..map_size.ilog2()).map(|stages| stages << stages).sum()
+}
+
+/// Nearest map size the pipeline supports: a power of two from `MIN_MAP_SIZE` to `MAX_MAP_SIZE`.
+/// Sizes between two powers of two round up.
+pub(crate) fn supported_map_size(map_size: i32) -> i32 {
+    (map_size.clamp(MIN_MAP_SIZE, MAX_MAP_SIZE) as u32).next_power_of_two() as i32
+}
+
+/// Size of a cascade's data in the FFT buffer, in vec2s: map_size² * 4 FFTs * 2 temp buffers.
+fn fft_data_size(map_size: i32) -> u32 {
+    (map_size * map_size) as u32 * 4 * 2
+}
+
+/// Size of the FFT buffer in bytes: the sum of every cascade's map_size² * 4 FFTs * 2 temp buffers
+/// * sizeof(vec2).
+fn fft_buffer_size(cascade_map_sizes: &[i32]) -> u64 {
+    cascade_map_sizes.iter().map(|&map_size| fft_data_size(map_size) as u64 * 8).sum()
+}
+
+/// Packs a push constant for dispatch, reporting oversized blocks instead of dispatching them.
+/// Reports a failed dispatch. Returns false if nothing was dispatched.
+fn dispatched(result: Result<(), DispatchError>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            godot_error!("wave_generator.rs: {e}");
+            false
+        }
+    }
+}
+
+fn pack_push_constant(push_constant: PushConstant) -> Option<PackedByteArray> {
+    match push_constant.into_packed() {
+        Ok(packed) => Some(packed),
+        Err(e) => {
+            godot_error!("wave_generator.rs: {}", e);
+            None
+        }
+    }
+}
+
+/// Time of a cascade at the given ocean time. Each cascade starts at its own phase so that
+/// cascades with similar tile lengths don't line up. With a `loop_period` the time wraps around,
+/// which keeps it precise since the waves repeat anyway.
+pub(crate) fn cascade_time(ocean_time: f64, cascade_index: usize, loop_period: f32) -> f32 {
+    let time = ocean_time + 120.0 + std::f64::consts::PI * cascade_index as f64;
+    if loop_period > 0.0 {
+        time.rem_euclid(loop_period as f64) as f32
+    } else {
+        time as f32
     }
 }
 
+/// Moves each cascade to `ocean_time` and updates its parameters that rely on time delta.
+/// Returns false if a cascade is null.
+pub(crate) fn advance_cascades(parameters: &Array<Option<Gd<WaveCascadeParameters>>>, delta: f64, ocean_time: f64) -> bool {
+    for i in 0..parameters.len() {
+        match parameters.at(i) {
+            Some(mut params_gd) => advance_cascade(&mut params_gd.bind_mut(), i, delta, ocean_time),
+            None => {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Moves a cascade to `ocean_time`, `delta` seconds after its previous update.
+pub(crate) fn advance_cascade(params: &mut WaveCascadeParameters, cascade_index: usize, delta: f64, ocean_time: f64) {
+    params.time = cascade_time(ocean_time, cascade_index, params.loop_period);
+    // Note: The constants are used to normalize parameters between 0 and 10.
+    params.foam_grow_rate = delta as f32 * params.foam_amount * 7.5;
+    params.foam_decay_rate = delta as f32 * (0.5f32.max(10.0 - params.foam_amount) * 1.15);
+}
+
+/// Picks the cascades to update from their priorities, the number of update periods since each
+/// was last updated. Due cascades, with a priority of at least 1, are taken most overdue first
+/// while their `costs` fit within `budget`. The most overdue cascade is always taken so that a
+/// small budget cannot stall the ocean. A budget of 0 is unlimited.
+pub(crate) fn schedule_cascades(priorities: &[f32], costs: &[i64], budget: i64) -> Vec<usize> {
+    let mut due: Vec<usize> = (0..priorities.len()).filter(|&i| priorities[i] >= 1.0).collect();
+    due.sort_by(|&a, &b| priorities[b].total_cmp(&priorities[a]));
+    let mut spent = 0;
+    due.retain(|&i| {
+        let fits = budget <= 0 || spent == 0 || spent + costs[i] <= budget;
+        if fits {
+            spent += costs[i];
+        }
+        fits
+    });
+    due
+}
+
 // Source: https://wikiwaves.org/Ocean-Wave_Spectra#JONSWAP_Spectrum
-fn jonswap_alpha(wind_speed: f32, fetch_length: f32) -> f32 {
-    0.076 * (wind_speed.powi(2) / (fetch_length * G)).powf(0.22)
+pub(crate) fn jonswap_alpha(wind_speed: f32, fetch_length: f32, gravity: f32) -> f32 {
+    0.076 * (wind_speed.powi(2) / (fetch_length * gravity)).powf(0.22)
 }
 
 // Source: https://wikiwaves.org/Ocean-Wave_Spectra#JONSWAP_Spectrum  
-fn jonswap_peak_angular_frequency(wind_speed: f32, fetch_length: f32) -> f32 {
-    22.0 * (GSQ / (wind_speed * fetch_length)).powf(1.0/3.0)
-}
\ No newline at end of file
+pub(crate) fn jonswap_peak_angular_frequency(wind_speed: f32, fetch_length: f32, gravity: f32) -> f32 {
+    22.0 * (gravity * gravity / (wind_speed * fetch_length)).powf(1.0/3.0)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn butterfly_factors_of_each_map_size_follow_the_previous() {
+        assert_eq!(butterfly_offset(MIN_MAP_SIZE), 0);
+        for map_size in [128, 256, 512, 1024, 2048] {
+            assert_eq!(butterfly_offset(map_size * 2), butterfly_offset(map_size) + map_size.ilog2() * map_size as u32);
+        }
+    }
+
+    #[test]
+    fn map_sizes_are_powers_of_two_in_range() {
+        assert_eq!(supported_map_size(1024), 1024);
+        assert_eq!(supported_map_size(1000), 1024);
+        assert_eq!(supported_map_size(0), MIN_MAP_SIZE);
+        assert_eq!(supported_map_size(-5), MIN_MAP_SIZE);
+        assert_eq!(supported_map_size(100_000), MAX_MAP_SIZE);
+    }
+
+    #[test]
+    fn fft_buffer_size_does_not_wrap() {
+        assert_eq!(fft_buffer_size(&[4096]), 1 << 30);
+        assert_eq!(fft_buffer_size(&[4096; 4]), 1 << 32);
+        assert!(fft_buffer_size(&[4096; 4]) > MAX_STORAGE_BUFFER_SIZE);
+        assert!(fft_buffer_size(&[4096, 2048, 256]) <= MAX_STORAGE_BUFFER_SIZE);
+    }
+
+    #[test]
+    fn scheduler_updates_most_overdue_cascades_within_budget() {
+        let costs = [256 * 256, 512 * 512, 256 * 256, 256 * 256];
+        // Not due yet
+        assert_eq!(schedule_cascades(&[0.5, 0.2, 0.9, 0.0], &costs, 0), Vec::<usize>::new());
+        // Unlimited budget
+        assert_eq!(schedule_cascades(&[1.0, 3.0, 0.5, f32::INFINITY], &costs, 0), vec![3, 1, 0]);
+        // The 512x512 cascade doesn't fit after the first, but a 256x256 one still does
+        assert_eq!(schedule_cascades(&[1.0, 3.0, 0.5, f32::INFINITY], &costs, 2 * 256 * 256), vec![3, 0]);
+        // The most overdue cascade updates even when it exceeds the budget
+        assert_eq!(schedule_cascades(&[1.0, 3.0, 0.5, 0.0], &costs, 1), vec![1]);
+    }
+}